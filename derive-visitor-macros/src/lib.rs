@@ -12,11 +12,19 @@ use std::{
     collections::{hash_map::Entry, HashMap},
     iter::IntoIterator,
 };
-use syn::token::Mut;
+use syn::{
+    ext::IdentExt,
+    parse::Parser,
+    parse_quote,
+    punctuated::Punctuated,
+    token::Mut,
+    visit::{self, Visit},
+    GenericArgument, PathArguments, Token, TypePath, WherePredicate,
+};
 use syn::{
     parse_macro_input, parse_str, spanned::Spanned, Attribute, Data, DataEnum, DataStruct,
-    DeriveInput, Error, Field, Fields, Ident, Lit, LitStr, Member, Meta, MetaList, NestedMeta,
-    Path, Result, Variant,
+    DeriveInput, Error, Field, Fields, Generics, Ident, Lit, LitInt, LitStr, Member, Meta,
+    MetaList, NestedMeta, Path, Result, Type, Variant,
 };
 
 #[proc_macro_derive(Visitor, attributes(visitor))]
@@ -137,6 +145,7 @@ impl Iterator for Params {
 enum Param {
     Unit(Path, Span),
     StringLiteral(Path, Span, LitStr),
+    IntegerLiteral(Path, Span, LitInt),
     NestedParams(Path, Span, Params),
 }
 
@@ -151,19 +160,18 @@ impl Param {
                 span,
                 Params::from_meta_list(meta_list)?,
             )),
-            Meta::NameValue(name_value) => {
-                if let Lit::Str(lit_str) = name_value.lit {
-                    Ok(Param::StringLiteral(path, span, lit_str))
-                } else {
-                    Err(Error::new_spanned(name_value, "invalid parameter"))
-                }
-            }
+            Meta::NameValue(name_value) => match name_value.lit {
+                Lit::Str(lit_str) => Ok(Param::StringLiteral(path, span, lit_str)),
+                Lit::Int(lit_int) => Ok(Param::IntegerLiteral(path, span, lit_int)),
+                lit => Err(Error::new_spanned(lit, "invalid parameter")),
+            },
         }
     }
     fn path(&self) -> &Path {
         match self {
             Self::Unit(path, _)
             | Self::StringLiteral(path, _, _)
+            | Self::IntegerLiteral(path, _, _)
             | Self::NestedParams(path, _, _) => path,
         }
     }
@@ -172,6 +180,7 @@ impl Param {
         match self {
             Self::Unit(_, span)
             | Self::StringLiteral(_, span, _)
+            | Self::IntegerLiteral(_, span, _)
             | Self::NestedParams(_, span, _) => *span,
         }
     }
@@ -191,6 +200,18 @@ impl Param {
             Err(Error::new(self.span(), "invalid parameter"))
         }
     }
+
+    fn integer_literal<N>(self) -> Result<N>
+    where
+        N: std::str::FromStr,
+        N::Err: std::fmt::Display,
+    {
+        if let Self::IntegerLiteral(_, _, lit_int) = self {
+            lit_int.base10_parse()
+        } else {
+            Err(Error::new(self.span(), "invalid parameter"))
+        }
+    }
 }
 
 struct VisitorItemParams {
@@ -210,56 +231,349 @@ fn visitor_method_name_from_path(struct_path: &Path, event: &str) -> Ident {
     )
 }
 
+fn visitor_item_params(param: Param, path: &Path) -> Result<VisitorItemParams> {
+    match param {
+        Param::Unit(_, _) => Ok(VisitorItemParams {
+            enter: Some(visitor_method_name_from_path(path, "enter")),
+            exit: Some(visitor_method_name_from_path(path, "exit")),
+        }),
+        Param::NestedParams(_, _, mut nested) => {
+            nested.validate(&["enter", "exit"])?;
+            Ok(VisitorItemParams {
+                enter: nested
+                    .param("enter")?
+                    .map(|param| visitor_method_name_from_param(param, path, "enter"))
+                    .transpose()?,
+                exit: nested
+                    .param("exit")?
+                    .map(|param| visitor_method_name_from_param(param, path, "exit"))
+                    .transpose()?,
+            })
+        }
+        Param::StringLiteral(_, _, lit) => Err(Error::new_spanned(lit, "invalid attribute")),
+        Param::IntegerLiteral(_, _, lit) => Err(Error::new_spanned(lit, "invalid attribute")),
+    }
+}
+
 fn visitor_method_name_from_param(param: Param, path: &Path, event: &str) -> Result<Ident> {
     match param {
         Param::StringLiteral(_, _, lit_str) => lit_str.parse(),
         Param::Unit(_, _) => Ok(visitor_method_name_from_path(path, event)),
-        Param::NestedParams(_, span, _) => Err(Error::new(span, "invalid parameter")),
+        Param::IntegerLiteral(_, span, _) | Param::NestedParams(_, span, _) => {
+            Err(Error::new(span, "invalid parameter"))
+        }
     }
 }
 
+// Generated code refers to derive-visitor as `_derive_visitor`, imported in an anonymous const
+// from the `crate` parameter (`::derive_visitor` by default), so that the derives work through re-exports
+fn import_crate(params: &mut Params) -> Result<TokenStream> {
+    let path = params
+        .param("crate")?
+        .map(|param| param.string_literal()?.parse::<Path>())
+        .transpose()?
+        .unwrap_or_else(|| parse_quote! { ::derive_visitor });
+    Ok(quote! {
+        use #path as _derive_visitor;
+    })
+}
+
 fn impl_visitor(input: DeriveInput, mutable: bool) -> Result<TokenStream> {
-    let params = Params::from_attrs(input.attrs, "visitor")?
+    let mut params = Params::from_attrs(input.attrs, "visitor")?;
+
+    let import_crate = import_crate(&mut params)?;
+
+    let break_type = params
+        .param("break")?
+        .map(|param| param.string_literal()?.parse::<Type>())
+        .transpose()?
+        .unwrap_or_else(|| parse_str("()").unwrap());
+
+    let hooks = visitor_hooks(&mut params)?;
+
+    // Ancestors are only tracked for immutable traversals
+    let ancestors = match params.param("ancestors")? {
+        Some(param) if mutable => {
+            return Err(Error::new(
+                param.span(),
+                "`ancestors` is only supported by `Visitor`, since a `VisitorMut` holds a mutable reference to the visited item",
+            ))
+        }
+        param => param.map(Param::unit).transpose()?.is_some(),
+    };
+
+    // Map entries are routed like items, but named after `Entry`, e.g. `enter_entry`
+    let entry_route = params
+        .param("entries")?
+        .map(|param| visitor_item_params(param, &parse_quote!(Entry)))
+        .transpose()?
+        .map(|item_params| visitor_entry_route(item_params, ancestors, mutable));
+
+    let params = params
         .map_ok(|param| {
             let path = param.path().clone();
+            Ok((path.clone(), visitor_item_params(param, &path)?))
+        })
+        .flatten()
+        .collect::<Result<HashMap<Path, VisitorItemParams>>>()?;
 
-            let item_params = match param {
-                Param::Unit(_, _) => VisitorItemParams {
-                    enter: Some(visitor_method_name_from_path(&path, "enter")),
-                    exit: Some(visitor_method_name_from_path(&path, "exit")),
-                },
-                Param::NestedParams(_, _, mut nested) => {
-                    nested.validate(&["enter", "exit"])?;
-                    VisitorItemParams {
-                        enter: nested
-                            .param("enter")?
-                            .map(|param| visitor_method_name_from_param(param, &path, "enter"))
-                            .transpose()?,
-                        exit: nested
-                            .param("exit")?
-                            .map(|param| visitor_method_name_from_param(param, &path, "exit"))
-                            .transpose()?,
-                    }
+    validate_visitor_data(&input.data)?;
+
+    let name = input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let routes = params
+        .into_iter()
+        .map(|(path, item_params)| visitor_route(&path, item_params, ancestors, mutable));
+    let impl_trait = Ident::new(
+        if mutable { "VisitorMut" } else { "Visitor" },
+        Span::call_site(),
+    );
+    let visit = visit_methods(routes, ancestors, mutable);
+    Ok(quote! {
+        const _: () = {
+            #import_crate
+
+            impl #impl_generics _derive_visitor::#impl_trait for #name #ty_generics #where_clause {
+                type Break = #break_type;
+
+                #visit
+                #entry_route
+
+                #hooks
+            }
+        };
+    })
+}
+
+// The `visit` method of a derived visitor, or `visit_with_ancestors` if its methods take the ancestors
+fn visit_methods(
+    routes: impl Iterator<Item = TokenStream>,
+    ancestors: bool,
+    mutable: bool,
+) -> TokenStream {
+    let mut_modifier = if mutable {
+        Some(Mut(Span::call_site()))
+    } else {
+        None
+    };
+    if ancestors {
+        quote! {
+            fn visit(
+                &mut self,
+                item: &dyn ::std::any::Any,
+                event: _derive_visitor::Event,
+            ) -> ::std::ops::ControlFlow<Self::Break, _derive_visitor::Children> {
+                _derive_visitor::Visitor::visit_with_ancestors(
+                    self,
+                    item,
+                    event,
+                    _derive_visitor::Ancestors::new(),
+                )
+            }
+
+            fn visit_with_ancestors(
+                &mut self,
+                item: &dyn ::std::any::Any,
+                event: _derive_visitor::Event,
+                ancestors: _derive_visitor::Ancestors<'_>,
+            ) -> ::std::ops::ControlFlow<Self::Break, _derive_visitor::Children> {
+                let mut children = _derive_visitor::Children::Visit;
+                #(
+                    #routes
+                )*
+                ::std::ops::ControlFlow::Continue(children)
+            }
+        }
+    } else {
+        quote! {
+            fn visit(
+                &mut self,
+                item: & #mut_modifier dyn ::std::any::Any,
+                event: _derive_visitor::Event,
+            ) -> ::std::ops::ControlFlow<Self::Break, _derive_visitor::Children> {
+                let mut children = _derive_visitor::Children::Visit;
+                #(
+                    #routes
+                )*
+                ::std::ops::ControlFlow::Continue(children)
+            }
+        }
+    }
+}
+
+// Hooks forwarding traversal information to the visitor's fields
+fn visitor_hooks(params: &mut Params) -> Result<TokenStream> {
+    let path_hooks = params
+        .param("path")?
+        .map(|param| param.string_literal()?.parse::<Member>())
+        .transpose()?
+        .map(|member| {
+            quote! {
+                fn push_path_segment(&mut self, segment: _derive_visitor::PathSegment<'_>) {
+                    self.#member.push(segment);
                 }
-                Param::StringLiteral(_, _, lit) => {
-                    return Err(Error::new_spanned(lit, "invalid attribute"));
+
+                fn pop_path_segment(&mut self) {
+                    self.#member.pop();
+                }
+            }
+        });
+
+    let context_hooks = params
+        .param("context")?
+        .map(|param| param.string_literal()?.parse::<Member>())
+        .transpose()?
+        .map(|member| {
+            quote! {
+                fn push_node(&mut self) {
+                    self.#member.push_node();
+                }
+
+                fn pop_node(&mut self) {
+                    self.#member.pop_node();
+                }
+            }
+        });
+
+    let shared_hooks = shared_hooks(params)?;
+    let container_hooks = position_hooks(params, "container", "Container")?;
+    let element_hooks = position_hooks(params, "element", "Element")?;
+
+    let poisoned_hook = params
+        .param("poisoned")?
+        .map(|param| poisoned_hook(&param.string_literal()?))
+        .transpose()?;
+
+    Ok(quote! {
+        #path_hooks
+        #context_hooks
+        #shared_hooks
+        #container_hooks
+        #element_hooks
+        #poisoned_hook
+    })
+}
+
+// Forwards container / element notifications to the methods named by `name(enter = "...", exit = "...")`.
+// The methods must be named explicitly, since the default names would shadow the hooks themselves.
+fn position_hooks(params: &mut Params, name: &str, arg_type: &str) -> Result<TokenStream> {
+    let mut nested = match params.param(name)? {
+        Some(Param::NestedParams(_, _, nested)) => nested,
+        Some(param) => return Err(Error::new(param.span(), "invalid parameter")),
+        None => return Ok(TokenStream::new()),
+    };
+    nested.validate(&["enter", "exit"])?;
+
+    let arg = Ident::new(name, Span::call_site());
+    let arg_type = Ident::new(arg_type, Span::call_site());
+    let mut hooks = TokenStream::new();
+    for event in ["enter", "exit"] {
+        if let Some(param) = nested.param(event)? {
+            let method = param.string_literal()?.parse::<Ident>()?;
+            let hook = Ident::new(&format!("{event}_{name}"), Span::call_site());
+            hooks.extend(quote! {
+                fn #hook(&mut self, #arg: _derive_visitor::#arg_type) {
+                    self.#method(#arg);
+                }
+            });
+        }
+    }
+    Ok(hooks)
+}
+
+fn poisoned_hook(policy: &LitStr) -> Result<TokenStream> {
+    let flow = match policy.value().as_str() {
+        "break" => quote! {
+            ::std::ops::ControlFlow::Break(::std::convert::From::from(_derive_visitor::Poisoned))
+        },
+        value => {
+            let policy = match value {
+                "recover" => "Recover",
+                "skip" => "Skip",
+                "panic" => "Panic",
+                _ => {
+                    return Err(Error::new_spanned(
+                        policy,
+                        "unknown policy, supported: recover, skip, panic, break",
+                    ))
                 }
             };
-            Ok((path, item_params))
+            let policy = Ident::new(policy, Span::call_site());
+            quote! {
+                ::std::ops::ControlFlow::Continue(_derive_visitor::PoisonPolicy::#policy)
+            }
+        }
+    };
+    Ok(quote! {
+        fn poisoned(
+            &mut self,
+        ) -> ::std::ops::ControlFlow<Self::Break, _derive_visitor::PoisonPolicy> {
+            #flow
+        }
+    })
+}
+
+fn shared_hooks(params: &mut Params) -> Result<Option<TokenStream>> {
+    let member = params
+        .param("shared")?
+        .map(|param| param.string_literal()?.parse::<Member>())
+        .transpose()?;
+    let revisit = params
+        .param("revisit")?
+        .map(|param| {
+            if member.is_none() {
+                return Err(Error::new(
+                    param.span(),
+                    "`revisit` requires the `shared` parameter",
+                ));
+            }
+            param.string_literal()?.parse::<Ident>()
         })
-        .flatten()
-        .collect::<Result<HashMap<Path, VisitorItemParams>>>()?;
+        .transpose()?;
+
+    let on_revisit = if let Some(method) = revisit {
+        quote! {
+            ::std::option::Option::Some(revisit) => {
+                self.#method(allocation, revisit);
+                _derive_visitor::Children::Skip
+            }
+        }
+    } else {
+        quote! {
+            ::std::option::Option::Some(_) => _derive_visitor::Children::Skip,
+        }
+    };
+
+    Ok(member.map(|member| {
+        quote! {
+            fn enter_shared(
+                &mut self,
+                allocation: _derive_visitor::Allocation,
+            ) -> _derive_visitor::Children {
+                match self.#member.enter(allocation) {
+                    ::std::option::Option::None => _derive_visitor::Children::Visit,
+                    #on_revisit
+                }
+            }
+
+            fn exit_shared(&mut self) {
+                self.#member.exit();
+            }
+        }
+    }))
+}
 
-    match input.data {
+fn validate_visitor_data(data: &Data) -> Result<()> {
+    match data {
         Data::Enum(enum_) => {
-            for variant in enum_.variants {
+            for variant in &enum_.variants {
                 if let Some(attr) = variant.attrs.first() {
                     return Err(Error::new_spanned(
                         attr,
                         "#[visitor] attribute can only be applied to enum or struct",
                     ));
                 }
-                for field in variant.fields {
+                for field in &variant.fields {
                     if let Some(attr) = field.attrs.first() {
                         return Err(Error::new_spanned(
                             attr,
@@ -270,7 +584,7 @@ fn impl_visitor(input: DeriveInput, mutable: bool) -> Result<TokenStream> {
             }
         }
         Data::Struct(struct_) => {
-            for field in struct_.fields {
+            for field in &struct_.fields {
                 if let Some(attr) = field.attrs.first() {
                     return Err(Error::new_spanned(
                         attr,
@@ -286,44 +600,36 @@ fn impl_visitor(input: DeriveInput, mutable: bool) -> Result<TokenStream> {
             ));
         }
     }
+    Ok(())
+}
 
-    let name = input.ident;
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
-    let routes = params
-        .into_iter()
-        .map(|(path, item_params)| visitor_route(&path, item_params, mutable));
-    let impl_trait = Ident::new(
-        if mutable { "VisitorMut" } else { "Visitor" },
-        Span::call_site(),
-    );
-    let mut_modifier = if mutable {
-        Some(Mut(Span::call_site()))
+fn visitor_route(
+    path: &Path,
+    item_params: VisitorItemParams,
+    ancestors: bool,
+    mutable: bool,
+) -> TokenStream {
+    // Visitors with the `ancestors` parameter lend them to every method
+    let args = if ancestors {
+        quote!(item, ancestors)
     } else {
-        None
+        quote!(item)
     };
-    Ok(quote! {
-        impl #impl_generics ::derive_visitor::#impl_trait for #name #ty_generics #where_clause {
-            fn visit(&mut self, item: & #mut_modifier dyn ::std::any::Any, event: ::derive_visitor::Event) {
-                #(
-                    #routes
-                )*
-            }
-        }
-    })
-}
-
-fn visitor_route(path: &Path, item_params: VisitorItemParams, mutable: bool) -> TokenStream {
     let enter = item_params.enter.map(|method_name| {
         quote! {
-            ::derive_visitor::Event::Enter => {
-                self.#method_name(item);
+            _derive_visitor::Event::Enter => {
+                if let _derive_visitor::Children::Skip =
+                    _derive_visitor::IntoControlFlow::into_control_flow(self.#method_name(#args))?
+                {
+                    children = _derive_visitor::Children::Skip;
+                }
             }
         }
     });
     let exit = item_params.exit.map(|method_name| {
         quote! {
-            ::derive_visitor::Event::Exit => {
-                self.#method_name(item);
+            _derive_visitor::Event::Exit => {
+                _derive_visitor::IntoControlFlow::into_control_flow(self.#method_name(#args))?;
             }
         }
     });
@@ -348,9 +654,103 @@ fn visitor_route(path: &Path, item_params: VisitorItemParams, mutable: bool) ->
     }
 }
 
+// The `visit_entry` method of a derived visitor. The types of the key and the value are inferred
+// from the parameter of the visitor's methods
+fn visitor_entry_route(
+    item_params: VisitorItemParams,
+    ancestors: bool,
+    mutable: bool,
+) -> TokenStream {
+    let args = if ancestors {
+        quote!(entry, ancestors)
+    } else {
+        quote!(entry)
+    };
+    let enter = item_params.enter.map(|method_name| {
+        quote! {
+            _derive_visitor::Event::Enter => {
+                if let _derive_visitor::Children::Skip =
+                    _derive_visitor::IntoControlFlow::into_control_flow(self.#method_name(#args))?
+                {
+                    children = _derive_visitor::Children::Skip;
+                }
+            }
+        }
+    });
+    let exit = item_params.exit.map(|method_name| {
+        quote! {
+            _derive_visitor::Event::Exit => {
+                _derive_visitor::IntoControlFlow::into_control_flow(self.#method_name(#args))?;
+            }
+        }
+    });
+    if enter.is_none() && exit.is_none() {
+        return TokenStream::new();
+    }
+    let route = quote! {
+        let mut children = _derive_visitor::Children::Visit;
+        if let Some(entry) = entry.downcast() {
+            match event {
+                #enter
+                #exit
+                _ => {}
+            }
+        }
+        ::std::ops::ControlFlow::Continue(children)
+    };
+
+    if mutable {
+        quote! {
+            fn visit_entry(
+                &mut self,
+                mut entry: _derive_visitor::MapEntryMut<'_>,
+                event: _derive_visitor::Event,
+            ) -> ::std::ops::ControlFlow<Self::Break, _derive_visitor::Children> {
+                #route
+            }
+        }
+    } else {
+        let ancestors = if ancestors {
+            quote!(ancestors)
+        } else {
+            quote!(_ancestors)
+        };
+        quote! {
+            fn visit_entry(
+                &mut self,
+                entry: _derive_visitor::MapEntry<'_>,
+                event: _derive_visitor::Event,
+                #ancestors: _derive_visitor::Ancestors<'_>,
+            ) -> ::std::ops::ControlFlow<Self::Break, _derive_visitor::Children> {
+                #route
+            }
+        }
+    }
+}
+
 fn impl_drive(input: DeriveInput, mutable: bool) -> Result<TokenStream> {
     let mut params = Params::from_attrs(input.attrs, "drive")?;
-    params.validate(&["skip"])?;
+    params.validate(&[
+        "skip",
+        "unshare",
+        "with",
+        "with_ref",
+        "with_mut",
+        "children",
+        "children_ref",
+        "children_mut",
+        "on_enter",
+        "on_enter_ref",
+        "on_enter_mut",
+        "on_exit",
+        "on_exit_ref",
+        "on_exit_mut",
+        "reverse",
+        "bound",
+        "crate",
+    ])?;
+
+    let import_crate = import_crate(&mut params)?;
 
     let skip_visit_self = params
         .param("skip")?
@@ -358,68 +758,485 @@ fn impl_drive(input: DeriveInput, mutable: bool) -> Result<TokenStream> {
         .transpose()?
         .is_some();
 
+    let reverse = params.param("reverse")?.map(Param::unit).transpose()?;
+
+    let drive_self = custom_driver(&mut params, "with", mutable)?.map(|(_, path)| path);
+
+    let unshare = params
+        .param("unshare")?
+        .map(Param::string_literal)
+        .transpose()?;
+
     let name = input.ident;
-    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let generics = drive_generics(
+        &input.generics,
+        &input.data,
+        &mut params,
+        drive_self.is_some(),
+        mutable,
+    )?;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let visitor = Ident::new(
-        if mutable { "VisitorMut" } else { "Visitor" },
+    let drive_fields = drive_data(input.data, drive_self, reverse.is_some(), mutable)?;
+    let drive_virtual_children = custom_driver(&mut params, "children", mutable)?
+        .map(|(_, children)| virtual_children(&children, mutable));
+    let drive_fields = quote! {
+        #drive_fields
+        #drive_virtual_children
+    };
+    let drive_self = if skip_visit_self {
+        drive_fields
+    } else {
+        visit_self(&drive_fields, mutable)
+    };
+    let drive_self = surround_with_hooks(&mut params, drive_self, mutable)?;
+
+    let impl_trait = Ident::new(
+        if mutable { "DriveMut" } else { "Drive" },
+        Span::call_site(),
+    );
+    let drive_node = Ident::new(
+        if mutable {
+            "drive_node_mut"
+        } else {
+            "drive_node"
+        },
         Span::call_site(),
     );
+    // `Arc<Self>` and `Rc<Self>` only implement `DriveMut`
+    let impl_unshare = match unshare {
+        Some(policy) if mutable => Some(impl_unshare(&name, &generics, &policy)?),
+        _ => None,
+    };
 
-    let enter_self = if skip_visit_self {
-        None
-    } else {
-        Some(quote! {
-            ::derive_visitor::#visitor::visit(visitor, self, ::derive_visitor::Event::Enter);
+    let drive_self = quote! {
+        _derive_visitor::#drive_node(visitor, |visitor| {
+            #drive_self
+            ::std::ops::ControlFlow::Continue(())
         })
     };
+    let methods = drive_methods(&drive_self, mutable);
 
-    let exit_self = if skip_visit_self {
-        None
+    Ok(quote! {
+        const _: () = {
+            #import_crate
+            #impl_unshare
+
+            impl #impl_generics _derive_visitor::#impl_trait for #name #ty_generics #where_clause {
+                #methods
+            }
+        };
+    })
+}
+
+// The methods of a derived `Drive` / `DriveMut` implementation, given the code driving the visitor through `self`
+fn drive_methods(drive_self: &TokenStream, mutable: bool) -> TokenStream {
+    // Ancestors are only tracked for immutable traversals
+    if mutable {
+        quote! {
+            fn drive_mut<V: _derive_visitor::VisitorMut>(
+                &mut self,
+                visitor: &mut V,
+            ) -> ::std::ops::ControlFlow<<V as _derive_visitor::VisitorMut>::Break> {
+                #drive_self
+            }
+        }
     } else {
-        Some(quote! {
-            ::derive_visitor::#visitor::visit(visitor, self, ::derive_visitor::Event::Exit);
-        })
-    };
+        quote! {
+            fn drive<V: _derive_visitor::Visitor>(
+                &self,
+                visitor: &mut V,
+            ) -> ::std::ops::ControlFlow<<V as _derive_visitor::Visitor>::Break> {
+                _derive_visitor::Drive::drive_with_ancestors(
+                    self,
+                    visitor,
+                    _derive_visitor::Ancestors::new(),
+                )
+            }
 
-    let drive_fields = match input.data {
-        Data::Struct(struct_) => drive_struct(struct_, mutable),
-        Data::Enum(enum_) => drive_enum(enum_, mutable),
-        Data::Union(union_) => {
-            return Err(Error::new_spanned(
-                union_.union_token,
-                "unions are not supported",
-            ));
+            fn drive_with_ancestors<V: _derive_visitor::Visitor>(
+                &self,
+                visitor: &mut V,
+                ancestors: _derive_visitor::Ancestors<'_>,
+            ) -> ::std::ops::ControlFlow<<V as _derive_visitor::Visitor>::Break> {
+                #drive_self
+            }
         }
-    }?;
+    }
+}
 
-    let impl_trait = Ident::new(
-        if mutable { "DriveMut" } else { "Drive" },
+// Makes the visitor enter `self`, drive through its fields unless asked to skip them, and exit `self`
+fn visit_self(drive_fields: &TokenStream, mutable: bool) -> TokenStream {
+    // Ancestors are only tracked for immutable traversals
+    if mutable {
+        return quote! {
+            if let _derive_visitor::Children::Visit =
+                _derive_visitor::VisitorMut::visit(visitor, self, _derive_visitor::Event::Enter)?
+            {
+                #drive_fields
+            }
+            _derive_visitor::VisitorMut::visit(visitor, self, _derive_visitor::Event::Exit)?;
+        };
+    }
+    quote! {
+        if let _derive_visitor::Children::Visit = _derive_visitor::Visitor::visit_with_ancestors(
+            visitor,
+            self,
+            _derive_visitor::Event::Enter,
+            ancestors,
+        )? {
+            ancestors.with_parent(self, |ancestors| {
+                #drive_fields
+                ::std::ops::ControlFlow::Continue(())
+            })?;
+        }
+        _derive_visitor::Visitor::visit_with_ancestors(
+            visitor,
+            self,
+            _derive_visitor::Event::Exit,
+            ancestors,
+        )?;
+    }
+}
+
+// Calls the `on_enter` hook before the traversal of `self`, and the `on_exit` hook after it,
+// even if the traversal is interrupted
+fn surround_with_hooks(
+    params: &mut Params,
+    drive_self: TokenStream,
+    mutable: bool,
+) -> Result<TokenStream> {
+    let visitor = Ident::new(
+        if mutable { "VisitorMut" } else { "Visitor" },
         Span::call_site(),
     );
-    let method = Ident::new(
-        if mutable { "drive_mut" } else { "drive" },
+    let on_enter = custom_driver(params, "on_enter", mutable)?.map(|(_, on_enter)| {
+        quote! {
+            #on_enter(self, visitor)?;
+        }
+    });
+    let drive_self = match custom_driver(params, "on_exit", mutable)? {
+        Some((_, on_exit)) => quote! {
+            let flow = (|visitor: &mut V| -> ::std::ops::ControlFlow<<V as _derive_visitor::#visitor>::Break> {
+                #drive_self
+                ::std::ops::ControlFlow::Continue(())
+            })(visitor);
+            let exit_flow = #on_exit(self, visitor);
+            flow?;
+            exit_flow?;
+        },
+        None => drive_self,
+    };
+    Ok(quote! {
+        #on_enter
+        #drive_self
+    })
+}
+
+// Drives the visitor through the value returned by a `children` method, after the fields
+fn virtual_children(children: &Path, mutable: bool) -> TokenStream {
+    if mutable {
+        quote! {
+            _derive_visitor::VirtualChildrenMut::drive_virtual_mut(#children(self), visitor)?;
+        }
+    } else {
+        quote! {
+            _derive_visitor::VirtualChildren::drive_virtual(#children(self), visitor, ancestors)?;
+        }
+    }
+}
+
+// Custom driver function of a type, a variant or a field (or a `children` method of a type).
+// `with` is used by both derives, `with_ref` / `with_mut` only by the `Drive` / `DriveMut` derive respectively.
+fn custom_driver(params: &mut Params, name: &str, mutable: bool) -> Result<Option<(Span, Path)>> {
+    let with = params.param(name)?;
+    let with_ref = params.param(&format!("{name}_ref"))?;
+    let with_mut = params.param(&format!("{name}_mut"))?;
+    if let (Some(_), Some(param)) = (&with, with_ref.as_ref().or(with_mut.as_ref())) {
+        return Err(Error::new(
+            param.span(),
+            format!("`{name}` can't be used together with `{name}_ref` or `{name}_mut`"),
+        ));
+    }
+    if mutable { with_mut } else { with_ref }
+        .or(with)
+        .map(|param| Ok((param.span(), param.string_literal()?.parse::<Path>()?)))
+        .transpose()
+}
+
+// Adds `T: 'static` for every type parameter, since `Drive` requires `Any`, and `T: Drive` / `T: DriveMut`
+// for the type parameters occurring in the types of the fields the visitor is driven through,
+// unless the bounds are given explicitly with `bound = "..."` or `bound(drive = "...", drive_mut = "...")`
+fn drive_generics(
+    generics: &Generics,
+    data: &Data,
+    params: &mut Params,
+    custom_driver: bool,
+    mutable: bool,
+) -> Result<Generics> {
+    let custom_bounds = params
+        .param("bound")?
+        .map(|param| match param {
+            Param::NestedParams(_, _, mut nested) => {
+                nested.validate(&["drive", "drive_mut"])?;
+                nested
+                    .param(if mutable { "drive_mut" } else { "drive" })?
+                    .map(Param::string_literal)
+                    .transpose()
+            }
+            param => param.string_literal().map(Some),
+        })
+        .transpose()?
+        .flatten()
+        .map(|bounds| {
+            Punctuated::<WherePredicate, Token![,]>::parse_terminated.parse_str(&bounds.value())
+        })
+        .transpose()?;
+
+    let bounded_types = if custom_bounds.is_some() || custom_driver {
+        Vec::new()
+    } else {
+        driven_field_types(data, mutable)?
+    };
+    let trait_ = Ident::new(
+        if mutable { "DriveMut" } else { "Drive" },
         Span::call_site(),
     );
-    let mut_modifier = if mutable {
-        Some(Mut(Span::call_site()))
-    } else {
-        None
+
+    let mut generics = generics.clone();
+    let type_params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let dependencies = Dependencies::of(&bounded_types, &type_params);
+    let where_clause = generics.make_where_clause();
+    for param in &type_params {
+        where_clause
+            .predicates
+            .push(parse_quote! { #param: 'static });
+        if dependencies.params.contains(param) {
+            where_clause
+                .predicates
+                .push(parse_quote! { #param: _derive_visitor::#trait_ });
+        }
+    }
+    for projection in dependencies.projections {
+        where_clause
+            .predicates
+            .push(parse_quote! { #projection: _derive_visitor::#trait_ });
+    }
+    where_clause
+        .predicates
+        .extend(custom_bounds.into_iter().flatten());
+    Ok(generics)
+}
+
+// Types of the fields that are driven through with `Drive` / `DriveMut`, rather than skipped or driven
+// through with a custom function
+fn driven_field_types(data: &Data, mutable: bool) -> Result<Vec<Type>> {
+    let driven = |attrs: &[Attribute]| -> Result<bool> {
+        let mut params = Params::from_attrs(attrs.to_vec(), "drive")?;
+        Ok(params.param("skip")?.is_none()
+            && params.param("leaf")?.is_none()
+            && custom_driver(&mut params, "with", mutable)?.is_none())
+    };
+    let mut types = Vec::new();
+    let mut add_fields = |fields: &Fields| -> Result<()> {
+        for field in fields {
+            if driven(&field.attrs)? {
+                types.push(driven_type(field, mutable)?);
+            }
+        }
+        Ok(())
     };
+    match data {
+        Data::Struct(struct_) => add_fields(&struct_.fields)?,
+        Data::Enum(enum_) => {
+            for variant in &enum_.variants {
+                if driven(&variant.attrs)? {
+                    add_fields(&variant.fields)?;
+                }
+            }
+        }
+        Data::Union(_) => {}
+    }
+    Ok(types)
+}
+
+// Mutable traversals of `entries` fields only drive the visitor through the values of the map,
+// so the type of the keys, i.e. the first type argument, only has to be `'static`
+fn driven_type(field: &Field, mutable: bool) -> Result<Type> {
+    let mut params = Params::from_attrs(field.attrs.clone(), "drive")?;
+    if !mutable || params.param("entries")?.is_none() {
+        return Ok(field.ty.clone());
+    }
+    if let Type::Path(TypePath { path, .. }) = &field.ty {
+        if let Some(PathArguments::AngleBracketed(arguments)) =
+            path.segments.last().map(|segment| &segment.arguments)
+        {
+            let mut types = arguments.args.iter().filter_map(|argument| match argument {
+                GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            });
+            if let (Some(_), Some(value)) = (types.next(), types.next()) {
+                return Ok(value.clone());
+            }
+        }
+    }
+    Ok(field.ty.clone())
+}
+
+// Type parameters and associated types of type parameters occurring in the types of driven fields.
+// Like serde, the derived implementations bound the associated types, e.g. `T::Item: Drive`,
+// rather than the type parameters they belong to
+struct Dependencies<'a> {
+    type_params: &'a [Ident],
+    params: Vec<Ident>,
+    projections: Vec<TypePath>,
+}
+
+impl<'a> Dependencies<'a> {
+    fn of(types: &[Type], type_params: &'a [Ident]) -> Self {
+        let mut dependencies = Self {
+            type_params,
+            params: Vec::new(),
+            projections: Vec::new(),
+        };
+        for ty in types {
+            dependencies.visit_type(ty);
+        }
+        dependencies
+    }
+
+    fn is_type_param(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Path(TypePath { qself: None, path }) => path
+                .get_ident()
+                .is_some_and(|ident| self.type_params.contains(ident)),
+            _ => false,
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Dependencies<'_> {
+    fn visit_type_path(&mut self, type_path: &'ast TypePath) {
+        // `PhantomData<T>` implements `Drive` for any `T`
+        if is_phantom_data(type_path) {
+            return;
+        }
+        let projection = match &type_path.qself {
+            // `<T as Trait>::Assoc`
+            Some(qself) => self.is_type_param(&qself.ty),
+            // `T::Assoc`
+            None => {
+                type_path.path.leading_colon.is_none()
+                    && type_path.path.segments.len() > 1
+                    && self.type_params.contains(&type_path.path.segments[0].ident)
+            }
+        };
+        if projection {
+            if !self.projections.contains(type_path) {
+                self.projections.push(type_path.clone());
+            }
+            return;
+        }
+        if let Some(ident) = type_path.path.get_ident() {
+            if type_path.qself.is_none()
+                && self.type_params.contains(ident)
+                && !self.params.contains(ident)
+            {
+                self.params.push(ident.clone());
+            }
+        }
+        visit::visit_type_path(self, type_path);
+    }
+}
+
+// `PhantomData`, `marker::PhantomData`, `std::marker::PhantomData` or `core::marker::PhantomData`
+fn is_phantom_data(type_path: &TypePath) -> bool {
+    if type_path.qself.is_some() {
+        return false;
+    }
+    let path = &type_path.path;
+    let idents = path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect::<Vec<_>>();
+    match idents.as_slice() {
+        [phantom_data] => path.leading_colon.is_none() && phantom_data == "PhantomData",
+        [marker, phantom_data] => {
+            path.leading_colon.is_none() && marker == "marker" && phantom_data == "PhantomData"
+        }
+        [krate, marker, phantom_data] => {
+            (krate == "std" || krate == "core")
+                && marker == "marker"
+                && phantom_data == "PhantomData"
+        }
+        _ => false,
+    }
+}
+
+// Drives the visitor through the fields of a struct or an enum, or through `self` with a custom function
+fn drive_data(
+    data: Data,
+    drive_self: Option<Path>,
+    reverse: bool,
+    mutable: bool,
+) -> Result<TokenStream> {
+    match (data, drive_self) {
+        (Data::Union(union_), _) => Err(Error::new_spanned(
+            union_.union_token,
+            "unions are not supported",
+        )),
+        (_, Some(drive_self)) if reverse => Err(Error::new_spanned(
+            drive_self,
+            "`reverse` can't be used together with a custom driver",
+        )),
+        (_, Some(drive_self)) => Ok(quote! {
+            #drive_self(self, visitor)?;
+        }),
+        (Data::Struct(struct_), None) => drive_struct(struct_, reverse, mutable),
+        (Data::Enum(enum_), None) => drive_enum(enum_, reverse, mutable),
+    }
+}
 
+fn impl_unshare(name: &Ident, generics: &Generics, policy: &LitStr) -> Result<TokenStream> {
+    let mut generics = generics.clone();
+    let unshare = match policy.value().as_str() {
+        "clone" => {
+            generics
+                .make_where_clause()
+                .predicates
+                .push(parse_quote! { Self: ::std::clone::Clone });
+            quote! { ::std::option::Option::Some(::std::clone::Clone::clone(self)) }
+        }
+        "skip" => quote! { ::std::option::Option::None },
+        "panic" => {
+            let message = format!("cannot drive a VisitorMut through a shared {name}");
+            quote! { ::std::panic!(#message) }
+        }
+        _ => {
+            return Err(Error::new_spanned(
+                policy,
+                "unknown policy, supported: clone, skip, panic",
+            ))
+        }
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
     Ok(quote! {
-        impl #impl_generics ::derive_visitor::#impl_trait for #name #ty_generics #where_clause {
-            fn #method<V: ::derive_visitor::#visitor>(& #mut_modifier self, visitor: &mut V) {
-                #enter_self
-                #drive_fields
-                #exit_self
+        impl #impl_generics _derive_visitor::Unshare for #name #ty_generics #where_clause {
+            fn unshare(&self) -> ::std::option::Option<Self> {
+                #unshare
             }
         }
     })
 }
 
-fn drive_struct(struct_: DataStruct, mutable: bool) -> Result<TokenStream> {
-    struct_
+fn drive_struct(struct_: DataStruct, reverse: bool, mutable: bool) -> Result<TokenStream> {
+    let field_count = struct_.fields.len();
+    let mut conditions = Vec::new();
+    let fields = struct_
         .fields
         .into_iter()
         .enumerate()
@@ -433,16 +1250,43 @@ fn drive_struct(struct_: DataStruct, mutable: bool) -> Result<TokenStream> {
             } else {
                 None
             };
-            drive_field(&quote! { & #mut_modifier self.#member }, field, mutable)
-        })
-        .collect()
+            let segments = field_path_segment(&field, index, field_count)
+                .into_iter()
+                .collect::<Vec<_>>();
+            drive_field(
+                &quote! { & #mut_modifier self.#member },
+                &quote! { &self.#member },
+                field,
+                &segments,
+                &mut conditions,
+                mutable,
+            )
+        });
+    let fields = ordered_fields(fields, reverse)?;
+    Ok(quote! {
+        #( #conditions )*
+        #fields
+    })
+}
+
+// Sorts the fields by their `order`, keeping the declaration order of fields with the same `order`
+fn ordered_fields(
+    fields: impl Iterator<Item = Result<(u32, TokenStream)>>,
+    reverse: bool,
+) -> Result<TokenStream> {
+    let mut fields = fields.collect::<Result<Vec<_>>>()?;
+    fields.sort_by_key(|(order, _)| *order);
+    if reverse {
+        fields.reverse();
+    }
+    Ok(fields.into_iter().map(|(_, field)| field).collect())
 }
 
-fn drive_enum(enum_: DataEnum, mutable: bool) -> Result<TokenStream> {
+fn drive_enum(enum_: DataEnum, reverse: bool, mutable: bool) -> Result<TokenStream> {
     let variants = enum_
         .variants
         .into_iter()
-        .map(|x| drive_variant(x, mutable))
+        .map(|x| drive_variant(x, reverse, mutable))
         .collect::<Result<TokenStream>>()?;
     Ok(quote! {
         match self {
@@ -452,37 +1296,120 @@ fn drive_enum(enum_: DataEnum, mutable: bool) -> Result<TokenStream> {
     })
 }
 
-fn drive_variant(variant: Variant, mutable: bool) -> Result<TokenStream> {
+fn drive_variant(variant: Variant, reverse: bool, mutable: bool) -> Result<TokenStream> {
     let mut params = Params::from_attrs(variant.attrs, "drive")?;
-    params.validate(&["skip"])?;
+    params.validate(&["skip", "skip_if", "with", "with_ref", "with_mut"])?;
     if params.param("skip")?.map(Param::unit).is_some() {
         return Ok(TokenStream::new());
     }
+    let skip_if = params
+        .param("skip_if")?
+        .map(|param| param.string_literal()?.parse::<Path>())
+        .transpose()?;
     let name = variant.ident;
-    let destructuring = destructure_fields(variant.fields.clone())?;
-    let fields = variant
-        .fields
-        .into_iter()
-        .enumerate()
-        .map(|(index, field)| {
-            drive_field(
-                &field
-                    .ident
-                    .clone()
-                    .unwrap_or_else(|| Ident::new(&format!("i{}", index), Span::call_site()))
-                    .to_token_stream(),
-                field,
-                mutable,
+    let variant_segment = path_segment("Variant", &name.unraw().to_string());
+    let mut conditions = Vec::new();
+
+    let (destructuring, fields) =
+        if let Some((_, drive_fn)) = custom_driver(&mut params, "with", mutable)? {
+            let bindings = field_bindings(&variant.fields);
+            let visitor = Ident::new(
+                if mutable { "VisitorMut" } else { "Visitor" },
+                Span::call_site(),
+            );
+            (
+                destructure(&variant.fields, &bindings),
+                quote! {
+                    _derive_visitor::#visitor::push_path_segment(visitor, #variant_segment);
+                    let flow = #drive_fn(#( #bindings, )* visitor);
+                    _derive_visitor::#visitor::pop_path_segment(visitor);
+                    flow?;
+                },
             )
-        })
-        .collect::<Result<TokenStream>>()?;
+        } else {
+            drive_variant_fields(
+                &name,
+                variant.fields,
+                &variant_segment,
+                &mut conditions,
+                reverse,
+                mutable,
+            )?
+        };
+
+    if skip_if.is_none() && conditions.is_empty() {
+        return Ok(quote! {
+            Self::#name#destructuring => {
+                #fields
+            }
+        });
+    }
+
+    // Predicates borrow the whole value, so they are evaluated before the fields are bound
+    let fields = quote! {
+        #( #conditions )*
+        match self {
+            Self::#name#destructuring => {
+                #fields
+            }
+            _ => {}
+        }
+    };
+    let fields = match skip_if {
+        Some(skip_if) => quote! {
+            if !#skip_if(&*self) {
+                #fields
+            }
+        },
+        None => fields,
+    };
     Ok(quote! {
-        Self::#name#destructuring => {
+        Self::#name { .. } => {
             #fields
         }
     })
 }
 
+fn drive_variant_fields(
+    name: &Ident,
+    fields: Fields,
+    variant_segment: &TokenStream,
+    conditions: &mut Vec<TokenStream>,
+    reverse: bool,
+    mutable: bool,
+) -> Result<(TokenStream, TokenStream)> {
+    let destructuring = destructure_fields(fields.clone())?;
+    let field_count = fields.len();
+    let fields = fields.into_iter().enumerate().map(|(index, field)| {
+        let segments = std::iter::once(variant_segment.clone())
+            .chain(field_path_segment(&field, index, field_count))
+            .collect::<Vec<_>>();
+        let member = field.ident.as_ref().map_or_else(
+            || Member::Unnamed(index.into()),
+            |ident| Member::Named(ident.clone()),
+        );
+        drive_field(
+            &field
+                .ident
+                .clone()
+                .unwrap_or_else(|| Ident::new(&format!("i{index}"), Span::call_site()))
+                .to_token_stream(),
+            // Predicates are evaluated before the fields are bound, see `drive_variant`
+            &quote! {
+                match &*self {
+                    Self::#name { #member: field, .. } => field,
+                    _ => ::std::unreachable!(),
+                }
+            },
+            field,
+            &segments,
+            conditions,
+            mutable,
+        )
+    });
+    Ok((destructuring, ordered_fields(fields, reverse)?))
+}
+
 fn destructure_fields(fields: Fields) -> Result<TokenStream> {
     Ok(match fields {
         Fields::Named(fields) => {
@@ -513,7 +1440,7 @@ fn destructure_fields(fields: Fields) -> Result<TokenStream> {
                     Ok(if params.param("skip")?.map(Param::unit).is_some() {
                         quote! { _ }
                     } else {
-                        Ident::new(&format!("i{}", index), Span::call_site()).into_token_stream()
+                        Ident::new(&format!("i{index}"), Span::call_site()).into_token_stream()
                     })
                 })
                 .collect::<Result<Vec<TokenStream>>>()?;
@@ -525,26 +1452,180 @@ fn destructure_fields(fields: Fields) -> Result<TokenStream> {
     })
 }
 
-fn drive_field(value_expr: &TokenStream, field: Field, mutable: bool) -> Result<TokenStream> {
-    let mut params = Params::from_attrs(field.attrs, "drive")?;
-    params.validate(&["skip", "with"])?;
+// Names of the bindings of all the fields of a variant, including the skipped ones
+fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            field
+                .ident
+                .clone()
+                .unwrap_or_else(|| Ident::new(&format!("i{index}"), Span::call_site()))
+        })
+        .collect()
+}
 
-    if params.param("skip")?.map(Param::unit).is_some() {
-        return Ok(TokenStream::new());
+fn destructure(fields: &Fields, bindings: &[Ident]) -> TokenStream {
+    match fields {
+        Fields::Named(_) => quote! { { #( #bindings ),* } },
+        Fields::Unnamed(_) => quote! { ( #( #bindings ),* ) },
+        Fields::Unit => TokenStream::new(),
+    }
+}
+
+fn path_segment(kind: &str, name: &str) -> TokenStream {
+    let kind = Ident::new(kind, Span::call_site());
+    quote! { _derive_visitor::PathSegment::#kind(#name) }
+}
+
+// A lone unnamed field (i.e. a newtype) is transparent in the path,
+// so that `items[3].Directory.items[0]` is not cluttered with `.0`
+fn field_path_segment(field: &Field, index: usize, field_count: usize) -> Option<TokenStream> {
+    match &field.ident {
+        Some(ident) => Some(path_segment("Field", &ident.unraw().to_string())),
+        None if field_count == 1 => None,
+        None => Some(path_segment("Field", &index.to_string())),
     }
+}
 
-    let drive_fn = params.param("with")?.map_or_else(
-        || {
+// Function driving the visitor through a field, and whether it takes the ancestors as well
+fn field_driver(params: &mut Params, mutable: bool) -> Result<(Path, bool)> {
+    // Built-in drivers of immutable traversals also take the ancestors
+    let mut drive_fns = Vec::new();
+    drive_fns
+        .extend(custom_driver(params, "with", mutable)?.map(|(span, path)| (span, path, false)));
+    if let Some(param) = params.param("rebuild")? {
+        let span = param.span();
+        param.unit()?;
+        // Only mutable traversals need to rebuild the collection
+        if mutable {
+            drive_fns.push((
+                span,
+                parse_str("_derive_visitor::DriveRebuild::drive_rebuild")?,
+                false,
+            ));
+        }
+    }
+    if let Some(param) = params.param("entries")? {
+        let span = param.span();
+        param.unit()?;
+        drive_fns.push((
+            span,
             parse_str(if mutable {
-                "::derive_visitor::DriveMut::drive_mut"
+                "_derive_visitor::drive_entries_mut"
             } else {
-                "::derive_visitor::Drive::drive"
-            })
-        },
-        |param| param.string_literal()?.parse::<Path>(),
-    )?;
+                "_derive_visitor::drive_entries"
+            })?,
+            !mutable,
+        ));
+    }
+    if let Some(param) = params.param("upgrade")? {
+        let span = param.span();
+        param.unit()?;
+        // Weak pointers only implement `Drive`
+        if !mutable {
+            drive_fns.push((span, parse_str("_derive_visitor::drive_upgraded")?, true));
+        }
+    }
+    if let Some(param) = params.param("leaf")? {
+        let span = param.span();
+        param.unit()?;
+        drive_fns.push((
+            span,
+            parse_str(if mutable {
+                "_derive_visitor::drive_leaf_mut"
+            } else {
+                "_derive_visitor::drive_leaf"
+            })?,
+            !mutable,
+        ));
+    }
+    if let Some((span, _, _)) = drive_fns.get(1) {
+        return Err(Error::new(
+            *span,
+            "only one custom driver (`with`, `with_ref` / `with_mut`, `rebuild`, `entries`, `leaf` or `upgrade`) can be used",
+        ));
+    }
 
-    Ok(quote! {
-        #drive_fn(#value_expr, visitor);
+    Ok(match drive_fns.pop() {
+        Some((_, drive_fn, takes_ancestors)) => (drive_fn, takes_ancestors),
+        None if mutable => (parse_str("_derive_visitor::DriveMut::drive_mut")?, false),
+        None => (
+            parse_str("_derive_visitor::Drive::drive_with_ancestors")?,
+            true,
+        ),
     })
 }
+
+fn drive_field(
+    value_expr: &TokenStream,
+    field_ref: &TokenStream,
+    field: Field,
+    segments: &[TokenStream],
+    conditions: &mut Vec<TokenStream>,
+    mutable: bool,
+) -> Result<(u32, TokenStream)> {
+    let mut params = Params::from_attrs(field.attrs, "drive")?;
+    params.validate(&[
+        "skip", "skip_if", "with", "with_ref", "with_mut", "rebuild", "entries", "leaf", "upgrade",
+        "order",
+    ])?;
+
+    if params.param("skip")?.map(Param::unit).is_some() {
+        return Ok((0, TokenStream::new()));
+    }
+
+    let order = params
+        .param("order")?
+        .map(Param::integer_literal)
+        .transpose()?
+        .unwrap_or(0);
+
+    let (drive_fn, takes_ancestors) = field_driver(&mut params, mutable)?;
+    let ancestors = if takes_ancestors {
+        Some(quote!(ancestors))
+    } else {
+        None
+    };
+
+    let visitor = Ident::new(
+        if mutable { "VisitorMut" } else { "Visitor" },
+        Span::call_site(),
+    );
+    let pop_segments = segments.iter().map(|_| {
+        quote! {
+            _derive_visitor::#visitor::pop_path_segment(visitor);
+        }
+    });
+
+    let drive = quote! {
+        {
+            #(
+                _derive_visitor::#visitor::push_path_segment(visitor, #segments);
+            )*
+            let flow = #drive_fn(#value_expr, visitor, #ancestors);
+            #( #pop_segments )*
+            flow?;
+        }
+    };
+
+    let drive = match params
+        .param("skip_if")?
+        .map(|param| param.string_literal()?.parse::<Path>())
+        .transpose()?
+    {
+        Some(skip_if) => {
+            let skip = Ident::new(&format!("skip_{}", conditions.len()), Span::call_site());
+            conditions.push(quote! {
+                let #skip = #skip_if(&*self, #field_ref);
+            });
+            quote! {
+                if !#skip #drive
+            }
+        }
+        None => drive,
+    };
+
+    Ok((order, drive))
+}
@@ -0,0 +1,76 @@
+use std::{any::Any, fmt};
+
+/// Chain of items enclosing the item that is currently being visited.
+///
+/// [`Drive`](crate::Drive) implementations pass the ancestors of every item to
+/// [`Visitor::visit_with_ancestors`](crate::Visitor::visit_with_ancestors), see
+/// [Ancestors](crate::Visitor#ancestors). They borrow the data structure the visitor is being driven through,
+/// so they can only be used while the visitor is visiting the item. Ancestors are only maintained for immutable
+/// traversals, since a [`VisitorMut`](crate::VisitorMut) holds a mutable reference to the visited item.
+///
+/// Every item the visitor has entered, and hasn't exited yet, is an ancestor of the items visited in the meantime.
+/// Types marked with `#[drive(skip)]` at the type level and containers like [`Vec`] or [`Option`]
+/// are never ancestors.
+#[derive(Clone, Copy, Default)]
+pub struct Ancestors<'a> {
+    link: Option<&'a Link<'a>>,
+}
+
+struct Link<'a> {
+    item: &'a dyn Any,
+    parent: Ancestors<'a>,
+}
+
+impl<'a> Ancestors<'a> {
+    /// Ancestors of the item a traversal starts from, i.e. none.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `f` with the ancestors of the children of `item`, i.e. these ancestors and `item` itself.
+    ///
+    /// [`Drive`](crate::Drive) implementations use this method to pass the ancestors of an item's children
+    /// to [`Drive::drive_with_ancestors`](crate::Drive::drive_with_ancestors), see
+    /// [Implementing manually](crate::Drive#implementing-manually).
+    pub fn with_parent<R>(self, item: &dyn Any, f: impl FnOnce(Ancestors<'_>) -> R) -> R {
+        let link = Link { item, parent: self };
+        f(Ancestors { link: Some(&link) })
+    }
+
+    /// Iterates over the ancestors, starting from the parent of the current item.
+    pub fn iter(self) -> impl Iterator<Item = &'a dyn Any> {
+        std::iter::successors(self.link, |link| link.parent.link).map(|link| link.item)
+    }
+
+    /// The parent of the current item, if any.
+    #[must_use]
+    pub fn parent(self) -> Option<&'a dyn Any> {
+        self.link.map(|link| link.item)
+    }
+
+    /// The closest ancestor of type `T`, if any.
+    #[must_use]
+    pub fn nearest<T: Any>(self) -> Option<&'a T> {
+        self.iter().find_map(<dyn Any>::downcast_ref)
+    }
+
+    /// Number of ancestors of the current item.
+    #[must_use]
+    pub fn len(self) -> usize {
+        self.iter().count()
+    }
+
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.link.is_none()
+    }
+}
+
+impl fmt::Debug for Ancestors<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ancestors")
+            .field("len", &self.len())
+            .finish()
+    }
+}
@@ -0,0 +1,98 @@
+use std::ops::ControlFlow;
+
+use crate::{Visitor, VisitorMut};
+
+/// Kind of a standard library container, see [`Container`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerKind {
+    /// An [`Option`], containing at most one element.
+    Option,
+    /// A [`Result`], whose only element is the `Ok` value. The visitor isn't driven through errors,
+    /// so the container is empty if it holds one.
+    Result,
+    /// A slice, an array, a [`Vec`], a [`VecDeque`](std::collections::VecDeque) or a
+    /// [`LinkedList`](std::collections::LinkedList).
+    Sequence,
+    /// A [`HashSet`](std::collections::HashSet), a [`BTreeSet`](std::collections::BTreeSet) or a
+    /// [`BinaryHeap`](std::collections::BinaryHeap).
+    Set,
+    /// A [`HashMap`](std::collections::HashMap) or a [`BTreeMap`](std::collections::BTreeMap),
+    /// whose elements are key-value pairs.
+    Map,
+}
+
+/// A container the visitor is driven through, reported to [`Visitor::enter_container`] and
+/// [`Visitor::exit_container`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Container {
+    pub kind: ContainerKind,
+    /// Number of elements in the container.
+    pub len: usize,
+}
+
+/// Position of an element within its container, reported to [`Visitor::enter_element`] and
+/// [`Visitor::exit_element`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Element {
+    pub index: usize,
+    /// Whether this is the first element of the container.
+    pub first: bool,
+    /// Whether this is the last element of the container.
+    pub last: bool,
+}
+
+impl Element {
+    fn new(index: usize, len: usize) -> Self {
+        Self {
+            index,
+            first: index == 0,
+            last: index + 1 == len,
+        }
+    }
+}
+
+// Drives a visitor through the elements of a container, keeping container and element
+// notifications balanced even if the traversal is interrupted
+pub(crate) fn drive_container<V: Visitor + ?Sized, T>(
+    visitor: &mut V,
+    kind: ContainerKind,
+    elements: impl ExactSizeIterator<Item = T>,
+    mut drive: impl FnMut(&mut V, usize, T) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    let container = Container {
+        kind,
+        len: elements.len(),
+    };
+    visitor.enter_container(container);
+    let flow = elements.enumerate().try_for_each(|(index, item)| {
+        let element = Element::new(index, container.len);
+        visitor.enter_element(element);
+        let flow = drive(visitor, index, item);
+        visitor.exit_element(element);
+        flow
+    });
+    visitor.exit_container(container);
+    flow
+}
+
+pub(crate) fn drive_container_mut<V: VisitorMut + ?Sized, T>(
+    visitor: &mut V,
+    kind: ContainerKind,
+    elements: impl ExactSizeIterator<Item = T>,
+    mut drive: impl FnMut(&mut V, usize, T) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    let container = Container {
+        kind,
+        len: elements.len(),
+    };
+    visitor.enter_container(container);
+    let flow = elements.enumerate().try_for_each(|(index, item)| {
+        let element = Element::new(index, container.len);
+        visitor.enter_element(element);
+        let flow = drive(visitor, index, item);
+        visitor.exit_element(element);
+        flow
+    });
+    visitor.exit_container(container);
+    flow
+}
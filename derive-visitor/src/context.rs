@@ -0,0 +1,51 @@
+/// Position of the current node within the traversal.
+///
+/// Add it to a visitor and let the visitor forward node notifications to it, e.g. with the `context`
+/// parameter of the derived [`Visitor`](crate::Visitor). While the visitor is entering or exiting a node,
+/// the context describes that node.
+///
+/// Every type with a derived [`Drive`](crate::Drive) implementation is a node, including the types marked
+/// with `#[drive(skip)]` at the type level, which the visitor never enters. Containers like [`Vec`] or
+/// [`Option`] are not nodes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Context {
+    indices: Vec<usize>,
+    nodes_driven: usize,
+}
+
+impl Context {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_node(&mut self) {
+        self.indices.push(self.nodes_driven);
+        self.nodes_driven += 1;
+    }
+
+    pub fn pop_node(&mut self) {
+        self.indices.pop();
+    }
+
+    /// Number of nodes enclosing the current node. The node the traversal started from has depth 0.
+    #[must_use]
+    pub fn depth(&self) -> usize {
+        self.indices.len().saturating_sub(1)
+    }
+
+    /// Position of the current node in pre-order, starting from 0.
+    #[must_use]
+    pub fn index(&self) -> usize {
+        self.indices.last().copied().unwrap_or_default()
+    }
+
+    /// Number of nodes the visitor has been driven through so far, including the current one.
+    ///
+    /// This counts driven nodes rather than visits: types marked with `#[drive(skip)]` at the type level
+    /// are counted even though the visitor never enters them, as are nodes whose children are skipped.
+    #[must_use]
+    pub fn nodes_driven(&self) -> usize {
+        self.nodes_driven
+    }
+}
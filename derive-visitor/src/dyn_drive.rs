@@ -0,0 +1,294 @@
+use std::{any::Any, ops::ControlFlow};
+
+use crate::{
+    Allocation, Ancestors, Children, Container, Drive, DriveMut, Element, Event, MapEntry,
+    MapEntryMut, PathSegment, PoisonPolicy, Visitor, VisitorMut,
+};
+
+/// Object safe version of [`Drive`], implemented for every type that implements [`Drive`].
+///
+/// Unlike `Drive`, it can be used to store heterogeneous items as trait objects, e.g. in a `Vec<Box<dyn DynDrive>>`.
+/// `Drive` is implemented for `dyn DynDrive` (optionally with `Send` and `Sync`), as well as for boxes and
+/// other smart pointers to it, so such items are traversed along with the rest of the data structure:
+///
+/// ```rust
+/// use derive_visitor::{visitor_enter_fn, Drive, DynDrive};
+///
+/// #[derive(Drive)]
+/// struct Document {
+///     blocks: Vec<Box<dyn DynDrive>>,
+/// }
+///
+/// #[derive(Drive)]
+/// struct Paragraph;
+///
+/// #[derive(Drive)]
+/// struct Chart(Vec<Paragraph>);
+///
+/// let document = Document {
+///     blocks: vec![Box::new(Paragraph), Box::new(Chart(vec![Paragraph, Paragraph]))],
+/// };
+///
+/// let mut paragraphs = 0;
+/// let _ = document.drive(&mut visitor_enter_fn(|_: &Paragraph| paragraphs += 1));
+/// assert_eq!(paragraphs, 3);
+/// ```
+pub trait DynDrive {
+    /// Drives a visitor through `self` like [`Drive::drive`] does.
+    ///
+    /// The visitor's break value is kept by the caller, see [`Drive`] for `dyn DynDrive`.
+    fn dyn_drive(&self, visitor: &mut dyn Visitor<Break = ()>) -> ControlFlow<()>;
+
+    /// Drives a visitor through `self` like [`Drive::drive_with_ancestors`] does.
+    fn dyn_drive_with_ancestors(
+        &self,
+        visitor: &mut dyn Visitor<Break = ()>,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<()>;
+}
+
+/// Object safe version of [`DriveMut`], implemented for every type that implements both [`Drive`]
+/// and [`DriveMut`].
+///
+/// See [`DynDrive`].
+pub trait DynDriveMut: DynDrive {
+    /// Drives a visitor through `self` like [`DriveMut::drive_mut`] does.
+    fn dyn_drive_mut(&mut self, visitor: &mut dyn VisitorMut<Break = ()>) -> ControlFlow<()>;
+}
+
+impl<T: Drive> DynDrive for T {
+    fn dyn_drive(&self, mut visitor: &mut dyn Visitor<Break = ()>) -> ControlFlow<()> {
+        self.drive(&mut visitor)
+    }
+
+    fn dyn_drive_with_ancestors(
+        &self,
+        mut visitor: &mut dyn Visitor<Break = ()>,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<()> {
+        self.drive_with_ancestors(&mut visitor, ancestors)
+    }
+}
+
+impl<T: Drive + DriveMut> DynDriveMut for T {
+    fn dyn_drive_mut(&mut self, mut visitor: &mut dyn VisitorMut<Break = ()>) -> ControlFlow<()> {
+        self.drive_mut(&mut visitor)
+    }
+}
+
+// Passes a visitor with any break type through `dyn_drive`, keeping the break value aside
+struct BreakStash<'a, V: ?Sized, B> {
+    visitor: &'a mut V,
+    break_value: Option<B>,
+}
+
+impl<'a, V: ?Sized, B> BreakStash<'a, V, B> {
+    fn new(visitor: &'a mut V) -> Self {
+        Self {
+            visitor,
+            break_value: None,
+        }
+    }
+
+    fn stash<C>(&mut self, flow: ControlFlow<B, C>) -> ControlFlow<(), C> {
+        match flow {
+            ControlFlow::Continue(value) => ControlFlow::Continue(value),
+            ControlFlow::Break(value) => {
+                self.break_value = Some(value);
+                ControlFlow::Break(())
+            }
+        }
+    }
+
+    fn into_flow(self) -> ControlFlow<B> {
+        match self.break_value {
+            Some(value) => ControlFlow::Break(value),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+impl<V: Visitor + ?Sized> Visitor for BreakStash<'_, V, V::Break> {
+    type Break = ();
+
+    fn visit(&mut self, item: &dyn Any, event: Event) -> ControlFlow<(), Children> {
+        let flow = self.visitor.visit(item, event);
+        self.stash(flow)
+    }
+
+    fn visit_with_ancestors(
+        &mut self,
+        item: &dyn Any,
+        event: Event,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<(), Children> {
+        let flow = self.visitor.visit_with_ancestors(item, event, ancestors);
+        self.stash(flow)
+    }
+
+    fn visit_entry(
+        &mut self,
+        entry: MapEntry<'_>,
+        event: Event,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<(), Children> {
+        let flow = self.visitor.visit_entry(entry, event, ancestors);
+        self.stash(flow)
+    }
+
+    fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+        self.visitor.push_path_segment(segment);
+    }
+
+    fn pop_path_segment(&mut self) {
+        self.visitor.pop_path_segment();
+    }
+
+    fn push_node(&mut self) {
+        self.visitor.push_node();
+    }
+
+    fn pop_node(&mut self) {
+        self.visitor.pop_node();
+    }
+
+    fn enter_shared(&mut self, allocation: Allocation) -> Children {
+        self.visitor.enter_shared(allocation)
+    }
+
+    fn exit_shared(&mut self) {
+        self.visitor.exit_shared();
+    }
+
+    fn enter_container(&mut self, container: Container) {
+        self.visitor.enter_container(container);
+    }
+
+    fn exit_container(&mut self, container: Container) {
+        self.visitor.exit_container(container);
+    }
+
+    fn enter_element(&mut self, element: Element) {
+        self.visitor.enter_element(element);
+    }
+
+    fn exit_element(&mut self, element: Element) {
+        self.visitor.exit_element(element);
+    }
+
+    fn poisoned(&mut self) -> ControlFlow<(), PoisonPolicy> {
+        let flow = self.visitor.poisoned();
+        self.stash(flow)
+    }
+}
+
+impl<V: VisitorMut + ?Sized> VisitorMut for BreakStash<'_, V, V::Break> {
+    type Break = ();
+
+    fn visit(&mut self, item: &mut dyn Any, event: Event) -> ControlFlow<(), Children> {
+        let flow = self.visitor.visit(item, event);
+        self.stash(flow)
+    }
+
+    fn visit_entry(&mut self, entry: MapEntryMut<'_>, event: Event) -> ControlFlow<(), Children> {
+        let flow = self.visitor.visit_entry(entry, event);
+        self.stash(flow)
+    }
+
+    fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+        self.visitor.push_path_segment(segment);
+    }
+
+    fn pop_path_segment(&mut self) {
+        self.visitor.pop_path_segment();
+    }
+
+    fn push_node(&mut self) {
+        self.visitor.push_node();
+    }
+
+    fn pop_node(&mut self) {
+        self.visitor.pop_node();
+    }
+
+    fn enter_shared(&mut self, allocation: Allocation) -> Children {
+        self.visitor.enter_shared(allocation)
+    }
+
+    fn exit_shared(&mut self) {
+        self.visitor.exit_shared();
+    }
+
+    fn enter_container(&mut self, container: Container) {
+        self.visitor.enter_container(container);
+    }
+
+    fn exit_container(&mut self, container: Container) {
+        self.visitor.exit_container(container);
+    }
+
+    fn enter_element(&mut self, element: Element) {
+        self.visitor.enter_element(element);
+    }
+
+    fn exit_element(&mut self, element: Element) {
+        self.visitor.exit_element(element);
+    }
+
+    fn poisoned(&mut self) -> ControlFlow<(), PoisonPolicy> {
+        let flow = self.visitor.poisoned();
+        self.stash(flow)
+    }
+}
+
+macro_rules! impl_drive_for_dyn {
+    ( $( $type:ty ),+ ) => {
+        $(
+            impl Drive for $type {
+                fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+                    let mut stash = BreakStash::new(visitor);
+                    let _ = self.dyn_drive(&mut stash);
+                    stash.into_flow()
+                }
+
+                fn drive_with_ancestors<V: Visitor>(
+                    &self,
+                    visitor: &mut V,
+                    ancestors: Ancestors<'_>,
+                ) -> ControlFlow<V::Break> {
+                    let mut stash = BreakStash::new(visitor);
+                    let _ = self.dyn_drive_with_ancestors(&mut stash, ancestors);
+                    stash.into_flow()
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_drive_mut_for_dyn {
+    ( $( $type:ty ),+ ) => {
+        $(
+            impl_drive_for_dyn!($type);
+
+            impl DriveMut for $type {
+                fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+                    let mut stash = BreakStash::new(visitor);
+                    let _ = self.dyn_drive_mut(&mut stash);
+                    stash.into_flow()
+                }
+            }
+        )+
+    };
+}
+
+impl_drive_for_dyn!(
+    dyn DynDrive,
+    dyn DynDrive + Send,
+    dyn DynDrive + Send + Sync
+);
+
+impl_drive_mut_for_dyn!(
+    dyn DynDriveMut,
+    dyn DynDriveMut + Send,
+    dyn DynDriveMut + Send + Sync
+);
@@ -0,0 +1,254 @@
+use std::{any::Any, fmt, ops::ControlFlow};
+
+use crate::{
+    container::{drive_container, drive_container_mut},
+    drive_node, drive_node_mut, drive_segment, drive_segment_mut, Ancestors, Children,
+    ContainerKind, Drive, DriveMut, Event, PathSegment, Visitor, VisitorMut,
+};
+
+/// A key-value pair of a map, visited as a single item.
+///
+/// The visitor is driven through the entries of map fields marked with `#[drive(entries)]`, see
+/// [`drive_entries`]. It enters the entry with [`Visitor::visit_entry`], then it is driven through the key
+/// and the value, and exits the entry. Since entries borrow the map, they are handed to the visitor
+/// with their types erased rather than visited like other items, and they are never [`Ancestors`].
+/// [`downcast`](MapEntry::downcast) recovers the types of the key and the value.
+///
+/// Derived visitors route entries to their methods with the `entries` parameter, which works like the
+/// parameters naming visited types, except that the methods are called `enter_entry` / `exit_entry` by default.
+/// They take the entry by value, and are only called for entries of the type they take:
+///
+/// ```rust
+/// use derive_visitor::{Drive, MapEntry, Visitor};
+/// use std::collections::BTreeMap;
+///
+/// #[derive(Drive)]
+/// struct Config {
+///     #[drive(entries)]
+///     limits: BTreeMap<Resource, Limit>,
+/// }
+///
+/// #[derive(Drive, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+/// enum Resource {
+///     Users,
+///     Files,
+/// }
+///
+/// #[derive(Drive)]
+/// struct Limit(#[drive(skip)] u32);
+///
+/// #[derive(Default, Visitor)]
+/// #[visitor(entries(enter))]
+/// struct Validator {
+///     invalid: Vec<Resource>,
+/// }
+///
+/// impl Validator {
+///     fn enter_entry(&mut self, entry: MapEntry<'_, Resource, Limit>) {
+///         let max = match entry.key() {
+///             Resource::Users => 100,
+///             Resource::Files => 10_000,
+///         };
+///         if entry.value().0 > max {
+///             self.invalid.push(*entry.key());
+///         }
+///     }
+/// }
+///
+/// let mut limits = BTreeMap::new();
+/// limits.insert(Resource::Users, Limit(1000));
+/// limits.insert(Resource::Files, Limit(1000));
+///
+/// let mut validator = Validator::default();
+/// let _ = Config { limits }.drive(&mut validator);
+/// assert_eq!(validator.invalid, [Resource::Users]);
+/// ```
+///
+/// Visitors interested in the entries of several maps should implement [`visit_entry`](Visitor::visit_entry)
+/// themselves.
+pub struct MapEntry<'a, K: ?Sized = dyn Any, V: ?Sized = dyn Any> {
+    key: &'a K,
+    value: &'a V,
+}
+
+impl<'a, K: ?Sized, V: ?Sized> MapEntry<'a, K, V> {
+    #[must_use]
+    pub fn key(self) -> &'a K {
+        self.key
+    }
+
+    #[must_use]
+    pub fn value(self) -> &'a V {
+        self.value
+    }
+}
+
+impl<'a> MapEntry<'a> {
+    /// Recovers the types of the key and the value, if they are `K` and `V`.
+    #[must_use]
+    pub fn downcast<K: Any, V: Any>(self) -> Option<MapEntry<'a, K, V>> {
+        Some(MapEntry {
+            key: self.key.downcast_ref()?,
+            value: self.value.downcast_ref()?,
+        })
+    }
+}
+
+impl<K: ?Sized, V: ?Sized> Clone for MapEntry<'_, K, V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K: ?Sized, V: ?Sized> Copy for MapEntry<'_, K, V> {}
+
+impl<K: fmt::Debug + ?Sized, V: fmt::Debug + ?Sized> fmt::Debug for MapEntry<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapEntry")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+/// A key-value pair of a map, visited as a single item during mutable traversals, see [`MapEntry`].
+///
+/// It is handed to [`VisitorMut::visit_entry`]. Only the value can be mutated, since map keys can't be
+/// mutated in place, and mutable traversals only drive the visitor through the value.
+pub struct MapEntryMut<'a, K: ?Sized = dyn Any, V: ?Sized = dyn Any> {
+    key: &'a K,
+    value: &'a mut V,
+}
+
+impl<'a, K: ?Sized, V: ?Sized> MapEntryMut<'a, K, V> {
+    #[must_use]
+    pub fn key(&self) -> &'a K {
+        self.key
+    }
+
+    #[must_use]
+    pub fn value(&self) -> &V {
+        self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut V {
+        self.value
+    }
+
+    #[must_use]
+    pub fn into_value(self) -> &'a mut V {
+        self.value
+    }
+
+    // Lends the entry to several visitors in turn
+    pub(crate) fn reborrow(&mut self) -> MapEntryMut<'_, K, V> {
+        MapEntryMut {
+            key: self.key,
+            value: self.value,
+        }
+    }
+}
+
+impl MapEntryMut<'_> {
+    /// Recovers the types of the key and the value, if they are `K` and `V`.
+    pub fn downcast<K: Any, V: Any>(&mut self) -> Option<MapEntryMut<'_, K, V>> {
+        Some(MapEntryMut {
+            key: self.key.downcast_ref()?,
+            value: self.value.downcast_mut()?,
+        })
+    }
+}
+
+impl<K: fmt::Debug + ?Sized, V: fmt::Debug + ?Sized> fmt::Debug for MapEntryMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MapEntryMut")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+
+fn drive_entry<K: Drive, V: Drive, Vis: Visitor>(
+    key: &K,
+    value: &V,
+    visitor: &mut Vis,
+    ancestors: Ancestors<'_>,
+) -> ControlFlow<Vis::Break> {
+    let entry: MapEntry<'_> = MapEntry { key, value };
+    drive_node(visitor, |visitor| {
+        if let Children::Visit = visitor.visit_entry(entry, Event::Enter, ancestors)? {
+            key.drive_with_ancestors(visitor, ancestors)?;
+            value.drive_with_ancestors(visitor, ancestors)?;
+        }
+        visitor.visit_entry(entry, Event::Exit, ancestors)?;
+        ControlFlow::Continue(())
+    })
+}
+
+fn drive_entry_mut<K: Any, V: DriveMut, Vis: VisitorMut>(
+    key: &K,
+    value: &mut V,
+    visitor: &mut Vis,
+) -> ControlFlow<Vis::Break> {
+    drive_node_mut(visitor, |visitor| {
+        let entry: MapEntryMut<'_> = MapEntryMut { key, value };
+        if let Children::Visit = visitor.visit_entry(entry, Event::Enter)? {
+            value.drive_mut(visitor)?;
+        }
+        let entry: MapEntryMut<'_> = MapEntryMut { key, value };
+        visitor.visit_entry(entry, Event::Exit)?;
+        ControlFlow::Continue(())
+    })
+}
+
+/// Drives a visitor through the entries of a map, visiting every key-value pair as a [`MapEntry`].
+///
+/// The derived [`Drive`] implementation uses this function for fields marked with `#[drive(entries)]`.
+pub fn drive_entries<'a, M, K, V, Vis>(
+    map: &'a M,
+    visitor: &mut Vis,
+    ancestors: Ancestors<'_>,
+) -> ControlFlow<Vis::Break>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    <&'a M as IntoIterator>::IntoIter: ExactSizeIterator,
+    K: Drive,
+    V: Drive,
+    Vis: Visitor,
+{
+    drive_container(
+        visitor,
+        ContainerKind::Map,
+        map.into_iter(),
+        |visitor, _, (key, value)| {
+            drive_segment(visitor, PathSegment::Key(key), |visitor| {
+                drive_entry(key, value, visitor, ancestors)
+            })
+        },
+    )
+}
+
+/// Mutable version of [`drive_entries`], used by the derived [`DriveMut`] implementation.
+/// Visits every key-value pair as a [`MapEntryMut`].
+pub fn drive_entries_mut<'a, M, K, V, Vis>(
+    map: &'a mut M,
+    visitor: &mut Vis,
+) -> ControlFlow<Vis::Break>
+where
+    &'a mut M: IntoIterator<Item = (&'a K, &'a mut V)>,
+    <&'a mut M as IntoIterator>::IntoIter: ExactSizeIterator,
+    K: Any,
+    V: DriveMut,
+    Vis: VisitorMut,
+{
+    drive_container_mut(
+        visitor,
+        ContainerKind::Map,
+        map.into_iter(),
+        |visitor, _, (key, value)| {
+            drive_segment_mut(visitor, PathSegment::Key(key), |visitor| {
+                drive_entry_mut(key, value, visitor)
+            })
+        },
+    )
+}
@@ -72,7 +72,8 @@
 //!     ],
 //! };
 //!
-//! example_directory.drive(&mut counter);
+//! // `counter` never stops the traversal early, see [Early termination](Visitor#early-termination)
+//! let _ = example_directory.drive(&mut counter);
 //!
 //! assert_eq!(counter.files, 2);
 //! assert_eq!(counter.directories, 3);
@@ -113,17 +114,31 @@
 //!     ]
 //! };
 //!
-//! my_tree.drive_mut(&mut Renamer{from: "old", to: "new"});
+//! let _ = my_tree.drive_mut(&mut Renamer{from: "old", to: "new"});
 //!
 //! assert_eq!(my_tree.name, "new parent");
 //! assert_eq!(my_tree.children[0].name, "new child");
 //! ```
 //!
+//! ## Migrating from 0.4
+//!
+//! Version 0.5 lets visitors stop the traversal early, see [Early termination](Visitor#early-termination).
+//! This changes the signatures of the core traits:
+//! - [`Visitor::visit`] and [`VisitorMut::visit`] return `ControlFlow<Self::Break, Children>`, and both traits
+//!   have a `Break` associated type. Hand-written visitors that never stop the traversal should declare
+//!   `type Break = ();` and return `ControlFlow::Continue(Children::Visit)`;
+//! - [`Drive::drive`] and [`DriveMut::drive_mut`] return the break value as a `ControlFlow<V::Break>`, which
+//!   must be used. If the visitor never stops the traversal, discard it with `let _ = item.drive(&mut visitor);`;
+//! - hand-written `Drive` implementations should propagate the `ControlFlow` returned by their children
+//!   with `?`, see [Implementing manually](Drive#implementing-manually).
+//!
+//! Derived visitors don't need any changes: their methods can still return `()`.
+//!
 //! ## Features
 //! - `std-types-drive` - implement [Drive](Drive) for primitive types and String type from std.
-//! It is [recommended](https://github.com/nikis05/derive-visitor/issues/3#issuecomment-1186690655) to
-//! either skip these types in your `Drive` implementation, or to wrap them with newtypes, so this feature
-//! is disabled by default. However it might be useful when driving through autogenerated structs.
+//!   It is [recommended](https://github.com/nikis05/derive-visitor/issues/3#issuecomment-1186690655) to
+//!   either skip these types in your `Drive` implementation, or to wrap them with newtypes, so this feature
+//!   is disabled by default. However it might be useful when driving through autogenerated structs.
 
 /// See [`Drive`].
 pub use derive_visitor_macros::Drive;
@@ -137,11 +152,42 @@ pub use derive_visitor_macros::Visitor;
 /// See [`VisitorMut`].
 pub use derive_visitor_macros::VisitorMut;
 
-use std::{any::Any, cell::Cell, marker::PhantomData};
+mod ancestors;
+mod container;
+mod context;
+mod dyn_drive;
+mod entry;
+mod path;
+mod poison;
+mod shared;
+mod tuple;
+mod virtual_children;
 
-#[cfg(feature = "std-types-drive")]
-use std::ops::{Range, RangeBounds, RangeFrom, RangeInclusive, RangeTo, RangeToInclusive};
-use std::sync::{Arc, Mutex, RwLock};
+pub use ancestors::Ancestors;
+pub use container::{Container, ContainerKind, Element};
+pub use context::Context;
+pub use dyn_drive::{DynDrive, DynDriveMut};
+pub use entry::{drive_entries, drive_entries_mut, MapEntry, MapEntryMut};
+pub use path::{Path, PathSegment};
+pub use poison::{PoisonPolicy, Poisoned};
+pub use shared::{Allocation, Allocations, Revisit};
+pub use virtual_children::{VirtualChildren, VirtualChildrenMut};
+
+use std::{
+    any::Any,
+    borrow::Cow,
+    cell::Cell,
+    marker::PhantomData,
+    ops::{ControlFlow, Deref, DerefMut},
+    pin::Pin,
+    rc::{self, Rc},
+};
+
+use std::sync::{self, Arc, Mutex, RwLock};
+
+use container::{drive_container, drive_container_mut};
+use poison::unpoison;
+use tuple::{forward, forward_children, Member, Signal};
 
 /// An interface for visiting arbitrary data structures.
 ///
@@ -179,6 +225,444 @@ use std::sync::{Arc, Mutex, RwLock};
 /// }
 /// ```
 ///
+/// ## Early termination
+///
+/// [`visit`](Visitor::visit) returns a [`ControlFlow`]. Returning [`ControlFlow::Break`] stops the
+/// traversal immediately: no further items are entered or exited, and the break value is handed back
+/// to the caller of [`Drive::drive`].
+///
+/// Methods of a derived visitor can return either `()` or `ControlFlow<Self::Break>`. The break type
+/// defaults to `()`, and can be changed with the `break` parameter:
+///
+/// ```rust
+/// use derive_visitor::{Drive, Visitor};
+/// use std::ops::ControlFlow;
+///
+/// #[derive(Drive)]
+/// struct Directory {
+///     #[drive(skip)]
+///     name: String,
+///     items: Vec<File>,
+/// }
+///
+/// #[derive(Drive)]
+/// struct File {
+///     #[drive(skip)]
+///     name: String,
+/// }
+///
+/// #[derive(Visitor)]
+/// #[visitor(File(enter), break = "String")]
+/// struct FindHidden;
+///
+/// impl FindHidden {
+///     fn enter_file(&mut self, file: &File) -> ControlFlow<String> {
+///         if file.name.starts_with('.') {
+///             ControlFlow::Break(file.name.clone())
+///         } else {
+///             ControlFlow::Continue(())
+///         }
+///     }
+/// }
+///
+/// let directory = Directory {
+///     name: "home".into(),
+///     items: vec![
+///         File { name: "README.md".into() },
+///         File { name: ".bashrc".into() },
+///     ],
+/// };
+///
+/// assert_eq!(directory.drive(&mut FindHidden), ControlFlow::Break(".bashrc".to_string()));
+/// ```
+///
+/// ## Skipping children
+///
+/// When answering [Event::Enter](Event::Enter), a visitor can continue with [`Children::Skip`]
+/// to tell the [`Drive`] implementation not to descend into the item's fields.
+/// The item is still exited as usual. Methods of a derived visitor can return
+/// [`Children`] or `ControlFlow<Self::Break, Children>` for that:
+///
+/// ```rust
+/// use derive_visitor::{Children, Drive, Visitor};
+///
+/// #[derive(Drive)]
+/// struct Module {
+///     #[drive(skip)]
+///     generated: bool,
+///     items: Vec<Module>,
+/// }
+///
+/// #[derive(Visitor, Default)]
+/// #[visitor(Module(enter))]
+/// struct ModuleCounter {
+///     modules: u32,
+/// }
+///
+/// impl ModuleCounter {
+///     fn enter_module(&mut self, module: &Module) -> Children {
+///         self.modules += 1;
+///         if module.generated {
+///             Children::Skip
+///         } else {
+///             Children::Visit
+///         }
+///     }
+/// }
+///
+/// let root = Module {
+///     generated: false,
+///     items: vec![
+///         Module { generated: true, items: vec![Module { generated: false, items: vec![] }] },
+///         Module { generated: false, items: vec![] },
+///     ],
+/// };
+///
+/// let mut counter = ModuleCounter::default();
+/// let _ = root.drive(&mut counter);
+/// assert_eq!(counter.modules, 3);
+/// ```
+///
+/// ## Field paths
+///
+/// While driving a visitor through their fields, variants, elements and map entries,
+/// [`Drive`] implementations announce every step with [`push_path_segment`](Visitor::push_path_segment)
+/// and [`pop_path_segment`](Visitor::pop_path_segment). A derived visitor can collect them into a
+/// [`Path`] using the `path` parameter, and use it to tell where the visited item is:
+///
+/// ```rust
+/// use derive_visitor::{Drive, Path, Visitor};
+///
+/// #[derive(Drive)]
+/// struct Directory {
+///     #[drive(skip)]
+///     name: String,
+///     items: Vec<DirectoryItem>,
+/// }
+///
+/// #[derive(Drive)]
+/// enum DirectoryItem {
+///     File(File),
+///     Directory(Directory),
+/// }
+///
+/// #[derive(Drive)]
+/// struct File {
+///     #[drive(skip)]
+///     name: String,
+/// }
+///
+/// #[derive(Visitor, Default)]
+/// #[visitor(File(enter), path = "path")]
+/// struct NameValidator {
+///     path: Path,
+///     errors: Vec<String>,
+/// }
+///
+/// impl NameValidator {
+///     fn enter_file(&mut self, file: &File) {
+///         if file.name.is_empty() {
+///             self.errors.push(format!("empty file name at {}", self.path));
+///         }
+///     }
+/// }
+///
+/// let root = Directory {
+///     name: "root".into(),
+///     items: vec![
+///         DirectoryItem::File(File { name: "README.md".into() }),
+///         DirectoryItem::Directory(Directory {
+///             name: "home".into(),
+///             items: vec![DirectoryItem::File(File { name: "".into() })],
+///         }),
+///     ],
+/// };
+///
+/// let mut validator = NameValidator::default();
+/// let _ = root.drive(&mut validator);
+/// assert_eq!(validator.errors, ["empty file name at items[1].Directory.items[0].File"]);
+/// ```
+///
+/// ## Traversal context
+///
+/// [`Drive`] implementations also notify the visitor whenever they start and finish driving
+/// it through a node, with [`push_node`](Visitor::push_node) and [`pop_node`](Visitor::pop_node).
+/// A derived visitor can collect these notifications into a [`Context`] using the `context` parameter,
+/// which tells the depth and the position of the item being visited:
+///
+/// ```rust
+/// use derive_visitor::{Context, Drive, Visitor};
+///
+/// #[derive(Drive)]
+/// struct Tree {
+///     children: Vec<Tree>,
+/// }
+///
+/// #[derive(Visitor, Default)]
+/// #[visitor(Tree(enter), context = "context")]
+/// struct DepthCollector {
+///     context: Context,
+///     depths: Vec<usize>,
+/// }
+///
+/// impl DepthCollector {
+///     fn enter_tree(&mut self, _tree: &Tree) {
+///         self.depths.push(self.context.depth());
+///     }
+/// }
+///
+/// let tree = Tree {
+///     children: vec![
+///         Tree { children: vec![Tree { children: vec![] }] },
+///         Tree { children: vec![] },
+///     ],
+/// };
+///
+/// let mut collector = DepthCollector::default();
+/// let _ = tree.drive(&mut collector);
+/// assert_eq!(collector.depths, [0, 1, 2, 1]);
+/// assert_eq!(collector.context.nodes_driven(), 4);
+/// ```
+///
+/// ## Ancestors
+///
+/// During immutable traversals, [`Drive`] implementations visit every item with
+/// [`visit_with_ancestors`](Visitor::visit_with_ancestors), passing the chain of items enclosing it
+/// as [`Ancestors`]. The ancestors borrow the data structure, so they can only be used during the visit.
+/// The methods of a derived visitor receive them as a second argument if the visitor has the `ancestors` parameter:
+///
+/// ```rust
+/// use derive_visitor::{Ancestors, Drive, Visitor};
+///
+/// #[derive(Drive)]
+/// struct Function {
+///     #[drive(skip)]
+///     name: String,
+///     body: Vec<Expr>,
+/// }
+///
+/// #[derive(Drive)]
+/// enum Expr {
+///     Return,
+///     Closure(Function),
+/// }
+///
+/// #[derive(Visitor, Default)]
+/// #[visitor(Expr(enter), ancestors)]
+/// struct ReturnCollector {
+///     returns_from: Vec<String>,
+/// }
+///
+/// impl ReturnCollector {
+///     fn enter_expr(&mut self, expr: &Expr, ancestors: Ancestors<'_>) {
+///         if let Expr::Return = expr {
+///             let function = ancestors.nearest::<Function>().unwrap();
+///             self.returns_from.push(function.name.clone());
+///         }
+///     }
+/// }
+///
+/// let main = Function {
+///     name: "main".into(),
+///     body: vec![
+///         Expr::Closure(Function { name: "callback".into(), body: vec![Expr::Return] }),
+///         Expr::Return,
+///     ],
+/// };
+///
+/// let mut collector = ReturnCollector::default();
+/// let _ = main.drive(&mut collector);
+/// assert_eq!(collector.returns_from, ["callback", "main"]);
+/// ```
+///
+/// Every method of such a visitor takes the ancestors. Types driven with a custom function (`#[drive(with = "...")]`)
+/// and `Drive` implementations that don't override [`drive_with_ancestors`](Drive::drive_with_ancestors)
+/// drive the visitor through their children without ancestors.
+///
+/// ## Shared values and cycles
+///
+/// [`Drive`] implementations of `Arc` and `Rc` call [`enter_shared`](Visitor::enter_shared) before driving the
+/// visitor through their value, and skip it if asked to. A derived visitor can keep track of the visited
+/// allocations in [`Allocations`] using the `shared` parameter, so that values shared by several pointers
+/// are only visited once, and cycles don't make the traversal run forever. The optional `revisit` parameter
+/// names a method that is called with every skipped [`Allocation`] and the [`Revisit`] reason:
+///
+/// ```rust
+/// use derive_visitor::{Allocation, Allocations, Drive, Revisit, Visitor};
+/// use std::rc::{Rc, Weak};
+///
+/// #[derive(Drive)]
+/// struct Node {
+///     children: Vec<Rc<Node>>,
+///     #[drive(upgrade)]
+///     parent: Weak<Node>,
+/// }
+///
+/// #[derive(Visitor, Default)]
+/// #[visitor(Node(enter), shared = "allocations", revisit = "revisit")]
+/// struct NodeCounter {
+///     allocations: Allocations,
+///     nodes: usize,
+///     back_references: usize,
+/// }
+///
+/// impl NodeCounter {
+///     fn enter_node(&mut self, _node: &Node) {
+///         self.nodes += 1;
+///     }
+///     fn revisit(&mut self, _allocation: Allocation, revisit: Revisit) {
+///         if let Revisit::Cycle = revisit {
+///             self.back_references += 1;
+///         }
+///     }
+/// }
+///
+/// let root = Rc::new_cyclic(|root| Node {
+///     children: vec![Rc::new(Node { children: vec![], parent: root.clone() })],
+///     parent: Weak::new(),
+/// });
+///
+/// let mut counter = NodeCounter::default();
+/// let _ = root.drive(&mut counter);
+/// assert_eq!((counter.nodes, counter.back_references), (2, 1));
+/// ```
+///
+/// ## Containers and elements
+///
+/// Standard library containers, like [`Vec`], [`Option`], [`Result`] or maps, aren't visited themselves. Instead, their
+/// [`Drive`] implementations surround their elements with [`enter_container`](Visitor::enter_container) /
+/// [`exit_container`](Visitor::exit_container) notifications, and every element with
+/// [`enter_element`](Visitor::enter_element) / [`exit_element`](Visitor::exit_element) notifications,
+/// which report the [`Container`] and the [`Element`] position. A derived visitor can forward them to
+/// its own methods with the `container` and `element` parameters:
+///
+/// ```rust
+/// use derive_visitor::{Container, Drive, Element, Visitor};
+///
+/// #[derive(Drive)]
+/// struct Call {
+///     #[drive(skip)]
+///     name: String,
+///     args: Vec<Arg>,
+/// }
+///
+/// #[derive(Drive)]
+/// struct Arg(#[drive(skip)] String);
+///
+/// #[derive(Visitor, Default)]
+/// #[visitor(
+///     Call(enter),
+///     Arg(enter),
+///     container(enter = "open", exit = "close"),
+///     element(exit = "separate")
+/// )]
+/// struct Printer {
+///     output: String,
+/// }
+///
+/// impl Printer {
+///     fn enter_call(&mut self, call: &Call) {
+///         self.output.push_str(&call.name);
+///     }
+///     fn enter_arg(&mut self, arg: &Arg) {
+///         self.output.push_str(&arg.0);
+///     }
+///     fn open(&mut self, _container: Container) {
+///         self.output.push('(');
+///     }
+///     fn close(&mut self, _container: Container) {
+///         self.output.push(')');
+///     }
+///     fn separate(&mut self, element: Element) {
+///         if !element.last {
+///             self.output.push_str(", ");
+///         }
+///     }
+/// }
+///
+/// let call = Call {
+///     name: "max".into(),
+///     args: vec![Arg("a".into()), Arg("b".into())],
+/// };
+/// let mut printer = Printer::default();
+/// let _ = call.drive(&mut printer);
+/// assert_eq!(printer.output, "max(a, b)");
+/// ```
+///
+/// ## Poisoned locks
+///
+/// [`Drive`] implementations of [`Mutex`] and [`RwLock`] ask the visitor what to do when the lock is poisoned,
+/// using [`poisoned`](Visitor::poisoned). By default the traversal panics. A derived visitor can choose
+/// another [`PoisonPolicy`] with the `poisoned` parameter, set to `"recover"`, `"skip"` or `"panic"`,
+/// or stop the traversal with `"break"`, which breaks with [`Poisoned`] converted into the visitor's break type:
+///
+/// ```ignore
+/// #[derive(Visitor)]
+/// #[visitor(File(enter), break = "ValidationError", poisoned = "break")]
+/// struct NameValidator;
+///
+/// impl From<Poisoned> for ValidationError {
+///     // ...
+/// }
+/// ```
+///
+/// ## Combining visitors
+///
+/// A tuple of visitors with the same break type is a visitor itself. It forwards every call
+/// to each of its elements in order, so that several visitors can share a single traversal:
+///
+/// ```rust
+/// use derive_visitor::{visitor_enter_fn, Drive};
+///
+/// #[derive(Drive)]
+/// struct Tree {
+///     #[drive(skip)]
+///     size: u32,
+///     children: Vec<Tree>,
+/// }
+///
+/// let tree = Tree {
+///     size: 3,
+///     children: vec![Tree { size: 5, children: vec![] }],
+/// };
+///
+/// let mut count = 0;
+/// let mut total_size = 0;
+/// let _ = tree.drive(&mut (
+///     visitor_enter_fn(|_: &Tree| count += 1),
+///     visitor_enter_fn(|tree: &Tree| total_size += tree.size),
+/// ));
+/// assert_eq!((count, total_size), (2, 8));
+/// ```
+///
+/// The traversal stops as soon as any of the visitors breaks. Otherwise each visitor is notified
+/// as if it was driven on its own: a visitor that asks to skip the children of an item, or the value
+/// of a shared allocation, isn't notified of anything until the item or allocation is exited, even if
+/// the other visitors are driven through them.
+///
+/// ## Visitors chosen at runtime
+///
+/// `Visitor` is implemented for `&mut V` and `Box<V>`, including trait objects like
+/// `Box<dyn Visitor<Break = ()>>`. Such visitors can be driven with [`Drive::drive_dyn`]:
+///
+/// ```rust
+/// use derive_visitor::{visitor_enter_fn, Drive, Visitor};
+///
+/// #[derive(Drive)]
+/// struct File;
+///
+/// let mut files = 0;
+/// let mut visitors: Vec<Box<dyn Visitor<Break = ()>>> = vec![
+///     Box::new(visitor_enter_fn(|_: &File| files += 1)),
+/// ];
+///
+/// for visitor in &mut visitors {
+///     let _ = File.drive_dyn(visitor.as_mut());
+/// }
+/// drop(visitors);
+/// assert_eq!(files, 1);
+/// ```
+///
 /// ## Visitor functions / closures
 /// If your visitor is only interested in some particular type, you don't have to declare a struct,
 /// you can just create a visitor from a closure or a function, e.g.:
@@ -238,8 +722,83 @@ use std::sync::{Arc, Mutex, RwLock};
 ///     }
 /// }
 /// ```
+///
+/// The generated code refers to this crate as `::derive_visitor`. If it is only available under another path,
+/// e.g. through a re-export, specify it with `#[visitor(crate = "path")]`.
 pub trait Visitor {
-    fn visit(&mut self, item: &dyn Any, event: Event);
+    /// Value returned from the traversal when the visitor stops it early.
+    type Break;
+
+    fn visit(&mut self, item: &dyn Any, event: Event) -> ControlFlow<Self::Break, Children>;
+
+    /// Visits an item like [`visit`](Visitor::visit), along with the items enclosing it, see [Ancestors](Visitor#ancestors).
+    ///
+    /// [`Drive`] implementations call this method rather than `visit`. Calls `visit` by default.
+    fn visit_with_ancestors(
+        &mut self,
+        item: &dyn Any,
+        event: Event,
+        _ancestors: Ancestors<'_>,
+    ) -> ControlFlow<Self::Break, Children> {
+        self.visit(item, event)
+    }
+
+    /// Visits an entry of a map field marked with `#[drive(entries)]`, see [`MapEntry`].
+    /// Returning [`Children::Skip`] on [`Event::Enter`] skips the key and the value. Visits them by default.
+    fn visit_entry(
+        &mut self,
+        _entry: MapEntry<'_>,
+        _event: Event,
+        _ancestors: Ancestors<'_>,
+    ) -> ControlFlow<Self::Break, Children> {
+        ControlFlow::Continue(Children::Visit)
+    }
+
+    /// Called before the visitor is driven through a field, a variant, an element of a sequence
+    /// or an entry of a map. Does nothing by default.
+    fn push_path_segment(&mut self, _segment: PathSegment<'_>) {}
+
+    /// Called after the visitor has been driven through the child announced by the
+    /// matching [`push_path_segment`](Visitor::push_path_segment). Does nothing by default.
+    fn pop_path_segment(&mut self) {}
+
+    /// Called before the visitor is driven through a node, see [`drive_node`]. Does nothing by default.
+    fn push_node(&mut self) {}
+
+    /// Called after the visitor has been driven through a node, see [`drive_node`]. Does nothing by default.
+    fn pop_node(&mut self) {}
+
+    /// Called when the visitor reaches the value of an `Arc` or an `Rc`, see [`drive_shared`].
+    /// Returning [`Children::Skip`] skips the value. Visits every value by default.
+    fn enter_shared(&mut self, _allocation: Allocation) -> Children {
+        Children::Visit
+    }
+
+    /// Called after every [`enter_shared`](Visitor::enter_shared), once the value has been visited or skipped.
+    /// Does nothing by default.
+    fn exit_shared(&mut self) {}
+
+    /// Called before the visitor is driven through the elements of a standard library container,
+    /// such as a [`Vec`], an [`Option`] or a map. Does nothing by default.
+    fn enter_container(&mut self, _container: Container) {}
+
+    /// Called after the visitor has been driven through the elements of the container announced by the
+    /// matching [`enter_container`](Visitor::enter_container), even if the traversal is interrupted.
+    /// Does nothing by default.
+    fn exit_container(&mut self, _container: Container) {}
+
+    /// Called before the visitor is driven through an element of a container. Does nothing by default.
+    fn enter_element(&mut self, _element: Element) {}
+
+    /// Called after the visitor has been driven through the element announced by the matching
+    /// [`enter_element`](Visitor::enter_element), even if the traversal is interrupted. Does nothing by default.
+    fn exit_element(&mut self, _element: Element) {}
+
+    /// Called when a [`Mutex`] or an [`RwLock`] the visitor is about to be driven through turns out to be poisoned.
+    /// Breaking stops the traversal, otherwise the returned [`PoisonPolicy`] is applied. Panics by default.
+    fn poisoned(&mut self) -> ControlFlow<Self::Break, PoisonPolicy> {
+        ControlFlow::Continue(PoisonPolicy::Panic)
+    }
 }
 
 /// An interface for visiting data structures and mutating them during the visit.
@@ -264,51 +823,369 @@ pub trait Visitor {
 /// }
 /// ```
 pub trait VisitorMut {
-    fn visit(&mut self, item: &mut dyn Any, event: Event);
-}
+    /// Value returned from the traversal when the visitor stops it early.
+    type Break;
 
-/// Create a visitor that only visits items of some specific type from a function or a closure.
-///
-/// ## Example
-/// ```rust
-/// use derive_visitor::{visitor_fn, Drive};
-/// # #[derive(Drive)] struct File;
-/// File.drive(&mut visitor_fn(|file: &File, event| {
-///     // ...your logic here
-/// }));
-/// ```
-pub fn visitor_fn<T, F: FnMut(&T, Event)>(fun: F) -> FnVisitor<T, F> {
-    FnVisitor {
-        marker: PhantomData,
-        fun,
+    fn visit(&mut self, item: &mut dyn Any, event: Event) -> ControlFlow<Self::Break, Children>;
+
+    /// See [`Visitor::visit_entry`]. Mutable traversals skip the key, see [`MapEntryMut`].
+    fn visit_entry(
+        &mut self,
+        _entry: MapEntryMut<'_>,
+        _event: Event,
+    ) -> ControlFlow<Self::Break, Children> {
+        ControlFlow::Continue(Children::Visit)
     }
-}
 
-/// Create a visitor that only visits items and mutates them with the given function
-///
-/// ## Example
-/// ```rust
-/// use derive_visitor::{visitor_fn_mut, DriveMut};
-/// # #[derive(DriveMut)] struct File;
-/// File.drive_mut(&mut visitor_fn_mut(|file: &mut File, event| {
-///     // ...your logic here
-/// }));
-/// ```
-pub fn visitor_fn_mut<T, F: FnMut(&mut T, Event)>(fun: F) -> FnVisitor<T, F> {
-    FnVisitor {
-        marker: PhantomData,
-        fun,
+    /// See [`Visitor::push_path_segment`].
+    fn push_path_segment(&mut self, _segment: PathSegment<'_>) {}
+
+    /// See [`Visitor::pop_path_segment`].
+    fn pop_path_segment(&mut self) {}
+
+    /// See [`Visitor::push_node`].
+    fn push_node(&mut self) {}
+
+    /// See [`Visitor::pop_node`].
+    fn pop_node(&mut self) {}
+
+    /// See [`Visitor::enter_shared`].
+    fn enter_shared(&mut self, _allocation: Allocation) -> Children {
+        Children::Visit
     }
-}
 
-/// Similar to [visitor_fn](visitor_fn), but the closure will only be called on [Event::Enter](Event::Enter).
-pub fn visitor_enter_fn<T, F: FnMut(&T)>(mut fun: F) -> FnVisitor<T, impl FnMut(&T, Event)> {
-    visitor_fn(move |item, event| {
-        if let Event::Enter = event {
-            fun(item);
-        }
-    })
-}
+    /// See [`Visitor::exit_shared`].
+    fn exit_shared(&mut self) {}
+
+    /// See [`Visitor::enter_container`].
+    fn enter_container(&mut self, _container: Container) {}
+
+    /// See [`Visitor::exit_container`].
+    fn exit_container(&mut self, _container: Container) {}
+
+    /// See [`Visitor::enter_element`].
+    fn enter_element(&mut self, _element: Element) {}
+
+    /// See [`Visitor::exit_element`].
+    fn exit_element(&mut self, _element: Element) {}
+
+    /// See [`Visitor::poisoned`].
+    fn poisoned(&mut self) -> ControlFlow<Self::Break, PoisonPolicy> {
+        ControlFlow::Continue(PoisonPolicy::Panic)
+    }
+}
+
+// Forwards visitors through references and smart pointers, so that visitors chosen at runtime
+// (`&mut dyn Visitor`, `Box<dyn Visitor>`) can be driven like any other visitor
+macro_rules! impl_visitor_for_pointer {
+    ( $( $type:ty ),+ ) => {
+        $(
+            impl<V: Visitor + ?Sized> Visitor for $type {
+                type Break = V::Break;
+
+                fn visit(&mut self, item: &dyn Any, event: Event) -> ControlFlow<V::Break, Children> {
+                    (**self).visit(item, event)
+                }
+
+                fn visit_with_ancestors(
+                    &mut self,
+                    item: &dyn Any,
+                    event: Event,
+                    ancestors: Ancestors<'_>,
+                ) -> ControlFlow<V::Break, Children> {
+                    (**self).visit_with_ancestors(item, event, ancestors)
+                }
+
+                fn visit_entry(
+                    &mut self,
+                    entry: MapEntry<'_>,
+                    event: Event,
+                    ancestors: Ancestors<'_>,
+                ) -> ControlFlow<V::Break, Children> {
+                    (**self).visit_entry(entry, event, ancestors)
+                }
+
+                fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+                    (**self).push_path_segment(segment);
+                }
+
+                fn pop_path_segment(&mut self) {
+                    (**self).pop_path_segment();
+                }
+
+                fn push_node(&mut self) {
+                    (**self).push_node();
+                }
+
+                fn pop_node(&mut self) {
+                    (**self).pop_node();
+                }
+
+                fn enter_shared(&mut self, allocation: Allocation) -> Children {
+                    (**self).enter_shared(allocation)
+                }
+
+                fn exit_shared(&mut self) {
+                    (**self).exit_shared();
+                }
+
+                fn enter_container(&mut self, container: Container) {
+                    (**self).enter_container(container);
+                }
+
+                fn exit_container(&mut self, container: Container) {
+                    (**self).exit_container(container);
+                }
+
+                fn enter_element(&mut self, element: Element) {
+                    (**self).enter_element(element);
+                }
+
+                fn exit_element(&mut self, element: Element) {
+                    (**self).exit_element(element);
+                }
+
+                fn poisoned(&mut self) -> ControlFlow<V::Break, PoisonPolicy> {
+                    (**self).poisoned()
+                }
+            }
+
+            impl<V: VisitorMut + ?Sized> VisitorMut for $type {
+                type Break = V::Break;
+
+                fn visit(&mut self, item: &mut dyn Any, event: Event) -> ControlFlow<V::Break, Children> {
+                    (**self).visit(item, event)
+                }
+
+                fn visit_entry(
+                    &mut self,
+                    entry: MapEntryMut<'_>,
+                    event: Event,
+                ) -> ControlFlow<V::Break, Children> {
+                    (**self).visit_entry(entry, event)
+                }
+
+                fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+                    (**self).push_path_segment(segment);
+                }
+
+                fn pop_path_segment(&mut self) {
+                    (**self).pop_path_segment();
+                }
+
+                fn push_node(&mut self) {
+                    (**self).push_node();
+                }
+
+                fn pop_node(&mut self) {
+                    (**self).pop_node();
+                }
+
+                fn enter_shared(&mut self, allocation: Allocation) -> Children {
+                    (**self).enter_shared(allocation)
+                }
+
+                fn exit_shared(&mut self) {
+                    (**self).exit_shared();
+                }
+
+                fn enter_container(&mut self, container: Container) {
+                    (**self).enter_container(container);
+                }
+
+                fn exit_container(&mut self, container: Container) {
+                    (**self).exit_container(container);
+                }
+
+                fn enter_element(&mut self, element: Element) {
+                    (**self).enter_element(element);
+                }
+
+                fn exit_element(&mut self, element: Element) {
+                    (**self).exit_element(element);
+                }
+
+                fn poisoned(&mut self) -> ControlFlow<V::Break, PoisonPolicy> {
+                    (**self).poisoned()
+                }
+            }
+        )+
+    };
+}
+
+impl_visitor_for_pointer!(&mut V, Box<V>);
+
+/// A visitor that can fail, driven with [`Drive::try_drive`].
+///
+/// The traversal stops at the first error, which is returned to the caller of [`Drive::try_drive`]:
+///
+/// ```rust
+/// use derive_visitor::{Drive, Event, TryVisitor};
+/// use std::any::Any;
+///
+/// #[derive(Drive)]
+/// struct Directory {
+///     items: Vec<File>,
+/// }
+///
+/// #[derive(Drive)]
+/// struct File {
+///     #[drive(skip)]
+///     name: String,
+/// }
+///
+/// struct NameValidator;
+///
+/// impl TryVisitor for NameValidator {
+///     type Error = String;
+///
+///     fn visit(&mut self, item: &dyn Any, _event: Event) -> Result<(), String> {
+///         match item.downcast_ref::<File>() {
+///             Some(file) if file.name.is_empty() => Err("empty file name".to_string()),
+///             _ => Ok(()),
+///         }
+///     }
+/// }
+///
+/// let directory = Directory {
+///     items: vec![File { name: "README.md".into() }, File { name: "".into() }],
+/// };
+///
+/// assert_eq!(directory.try_drive(&mut NameValidator), Err("empty file name".to_string()));
+/// ```
+///
+/// This is a shorthand for a [`Visitor`] that breaks with its errors, which can also skip children
+/// and receive traversal information.
+pub trait TryVisitor {
+    type Error;
+
+    /// # Errors
+    ///
+    /// Returning an error stops the traversal.
+    fn visit(&mut self, item: &dyn Any, event: Event) -> Result<(), Self::Error>;
+
+    /// See [`Visitor::poisoned`].
+    ///
+    /// # Errors
+    ///
+    /// Returning an error stops the traversal.
+    fn poisoned(&mut self) -> Result<PoisonPolicy, Self::Error> {
+        Ok(PoisonPolicy::Panic)
+    }
+}
+
+/// A visitor that can fail and mutate the visited items, driven with [`DriveMut::try_drive_mut`].
+///
+/// It works exactly the same as [`TryVisitor`], but it takes a mutable reference to the visited element.
+pub trait TryVisitorMut {
+    type Error;
+
+    /// # Errors
+    ///
+    /// Returning an error stops the traversal.
+    fn visit(&mut self, item: &mut dyn Any, event: Event) -> Result<(), Self::Error>;
+
+    /// See [`Visitor::poisoned`].
+    ///
+    /// # Errors
+    ///
+    /// Returning an error stops the traversal.
+    fn poisoned(&mut self) -> Result<PoisonPolicy, Self::Error> {
+        Ok(PoisonPolicy::Panic)
+    }
+}
+
+/// Tells a [`Drive`] implementation whether to drive the visitor through the children
+/// of an item it has just entered.
+///
+/// Only the value returned for [Event::Enter](Event::Enter) is taken into account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Children {
+    /// Drive the visitor through the item's fields / variant.
+    #[default]
+    Visit,
+    /// Proceed straight to exiting the item.
+    Skip,
+}
+
+/// Return value of a derived visitor method.
+///
+/// Derived [`Visitor`] and [`VisitorMut`] implementations accept methods that return `()`,
+/// which always continues the traversal, [`Children`], or a [`ControlFlow`] with the visitor's
+/// break type that continues with either `()` or [`Children`].
+pub trait IntoControlFlow<B> {
+    fn into_control_flow(self) -> ControlFlow<B, Children>;
+}
+
+impl<B> IntoControlFlow<B> for () {
+    fn into_control_flow(self) -> ControlFlow<B, Children> {
+        ControlFlow::Continue(Children::Visit)
+    }
+}
+
+impl<B> IntoControlFlow<B> for Children {
+    fn into_control_flow(self) -> ControlFlow<B, Children> {
+        ControlFlow::Continue(self)
+    }
+}
+
+impl<B> IntoControlFlow<B> for ControlFlow<B> {
+    fn into_control_flow(self) -> ControlFlow<B, Children> {
+        match self {
+            ControlFlow::Continue(()) => ControlFlow::Continue(Children::Visit),
+            ControlFlow::Break(value) => ControlFlow::Break(value),
+        }
+    }
+}
+
+impl<B> IntoControlFlow<B> for ControlFlow<B, Children> {
+    fn into_control_flow(self) -> ControlFlow<B, Children> {
+        self
+    }
+}
+
+/// Create a visitor that only visits items of some specific type from a function or a closure.
+///
+/// ## Example
+/// ```rust
+/// use derive_visitor::{visitor_fn, Drive};
+/// # #[derive(Drive)] struct File;
+/// File.drive(&mut visitor_fn(|file: &File, event| {
+///     // ...your logic here
+/// }));
+/// ```
+pub fn visitor_fn<T, F: FnMut(&T, Event)>(fun: F) -> FnVisitor<T, F> {
+    FnVisitor {
+        marker: PhantomData,
+        fun,
+    }
+}
+
+/// Create a visitor that only visits items and mutates them with the given function
+///
+/// ## Example
+/// ```rust
+/// use derive_visitor::{visitor_fn_mut, DriveMut};
+/// # #[derive(DriveMut)] struct File;
+/// File.drive_mut(&mut visitor_fn_mut(|file: &mut File, event| {
+///     // ...your logic here
+/// }));
+/// ```
+pub fn visitor_fn_mut<T, F: FnMut(&mut T, Event)>(fun: F) -> FnVisitor<T, F> {
+    FnVisitor {
+        marker: PhantomData,
+        fun,
+    }
+}
+
+/// Similar to [visitor_fn](visitor_fn), but the closure will only be called on [Event::Enter](Event::Enter).
+pub fn visitor_enter_fn<T, F: FnMut(&T)>(mut fun: F) -> FnVisitor<T, impl FnMut(&T, Event)> {
+    visitor_fn(move |item, event| {
+        if let Event::Enter = event {
+            fun(item);
+        }
+    })
+}
 
 /// Similar to [`visitor_fn_mut`], but the closure will only be called on [Event::Enter](Event::Enter).
 pub fn visitor_enter_fn_mut<T, F: FnMut(&mut T)>(
@@ -328,24 +1205,31 @@ pub struct FnVisitor<T, F> {
 }
 
 impl<T: Any, F: FnMut(&T, Event)> Visitor for FnVisitor<T, F> {
-    fn visit(&mut self, item: &dyn Any, event: Event) {
+    type Break = ();
+
+    fn visit(&mut self, item: &dyn Any, event: Event) -> ControlFlow<(), Children> {
         if let Some(item) = <dyn Any>::downcast_ref::<T>(item) {
             let fun = &mut self.fun;
             fun(item, event);
         }
+        ControlFlow::Continue(Children::Visit)
     }
 }
 
 impl<T: Any, F: FnMut(&mut T, Event)> VisitorMut for FnVisitor<T, F> {
-    fn visit(&mut self, item: &mut dyn Any, event: Event) {
+    type Break = ();
+
+    fn visit(&mut self, item: &mut dyn Any, event: Event) -> ControlFlow<(), Children> {
         if let Some(item) = <dyn Any>::downcast_mut::<T>(item) {
             let fun = &mut self.fun;
             fun(item, event);
         }
+        ControlFlow::Continue(Children::Visit)
     }
 }
 
 /// Defines whether an item is being entered or exited by a visitor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Event {
     Enter,
     Exit,
@@ -393,36 +1277,76 @@ pub enum Event {
 ///
 /// ```ignore
 /// impl Drive for Directory {
-///     fn drive<V: Visitor>(&self, visitor: &mut V) {
-///         visitor.visit(self, Event::Enter);
-///         self.items.drive(visitor);
-///         visitor.visit(self, Event::Exit);
+///     fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+///         self.drive_with_ancestors(visitor, Ancestors::new())
+///     }
+///
+///     fn drive_with_ancestors<V: Visitor>(
+///         &self,
+///         visitor: &mut V,
+///         ancestors: Ancestors<'_>,
+///     ) -> ControlFlow<V::Break> {
+///         drive_node(visitor, |visitor| {
+///             if let Children::Visit = visitor.visit_with_ancestors(self, Event::Enter, ancestors)? {
+///                 ancestors.with_parent(self, |ancestors| {
+///                     self.items.drive_with_ancestors(visitor, ancestors)
+///                 })?;
+///             }
+///             visitor.visit_with_ancestors(self, Event::Exit, ancestors)?;
+///             ControlFlow::Continue(())
+///         })
 ///     }
 /// }
 ///
 /// impl Drive for DirectoryItem {
-///     fn drive<V: Visitor>(&self, visitor: &mut V) {
-///         visitor.visit(self, Event::Enter);
-///         match self {
-///             Self::File(file) => {
-///                 file.drive(visitor);
-///             },
-///             Self::Directory(directory) => {
-///                 directory.drive(visitor);
+///     fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+///         self.drive_with_ancestors(visitor, Ancestors::new())
+///     }
+///
+///     fn drive_with_ancestors<V: Visitor>(
+///         &self,
+///         visitor: &mut V,
+///         ancestors: Ancestors<'_>,
+///     ) -> ControlFlow<V::Break> {
+///         drive_node(visitor, |visitor| {
+///             if let Children::Visit = visitor.visit_with_ancestors(self, Event::Enter, ancestors)? {
+///                 ancestors.with_parent(self, |ancestors| match self {
+///                     Self::File(file) => file.drive_with_ancestors(visitor, ancestors),
+///                     Self::Directory(directory) => directory.drive_with_ancestors(visitor, ancestors),
+///                 })?;
 ///             }
-///         }
-///         visitor.visit(self, Event::Exit);
+///             visitor.visit_with_ancestors(self, Event::Exit, ancestors)?;
+///             ControlFlow::Continue(())
+///         })
 ///     }
 /// }
 ///
 /// impl Drive for File {
-///     fn drive<V: Visitor>(&self, visitor: &mut V) {
-///         visitor.visit(self, Event::Enter);
-///         visitor.visit(self, Event::Exit);
+///     fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+///         self.drive_with_ancestors(visitor, Ancestors::new())
+///     }
+///
+///     fn drive_with_ancestors<V: Visitor>(
+///         &self,
+///         visitor: &mut V,
+///         ancestors: Ancestors<'_>,
+///     ) -> ControlFlow<V::Break> {
+///         drive_leaf(self, visitor, ancestors)
 ///     }
 /// }
 /// ```
 ///
+/// Every implementation must return as soon as the visitor, or any nested item, returns
+/// [`ControlFlow::Break`], handing the break value back to its caller, and must not drive
+/// the visitor through the children of an item entered with [`Children::Skip`].
+/// Implementations that want their children to show up in a [`Path`] should wrap driving
+/// every child in [`push_path_segment`](Visitor::push_path_segment) /
+/// [`pop_path_segment`](Visitor::pop_path_segment) calls, even if the traversal is interrupted.
+/// Similarly, implementations for node types should use [`drive_node`] to keep the [`Context`] up to date.
+/// To keep the [`Ancestors`] up to date, implementations should implement
+/// [`drive_with_ancestors`](Drive::drive_with_ancestors), and pass the ancestors of their children,
+/// obtained with [`Ancestors::with_parent`], to the children's `drive_with_ancestors`.
+///
 /// ## Macro attributes
 ///
 /// The derived implementation of `Drive` can be customized using attributes:
@@ -434,12 +1358,120 @@ pub enum Event {
 ///
 /// If applied to a struct or an enum itself, the derived implementation will
 /// drive the visitor through the type's fields / variants, but won't make it
-/// enter or exit the type itself.
+/// enter or exit the type itself. Since the visitor never enters such a type,
+/// it has no opportunity to skip its children either.
+///
+/// ### `#[drive(skip_if = "path")]`
+///
+/// If applied to a field or an enum variant, the derived implementation won't drive the visitor through
+/// that field / variant when the given predicate returns `true`. The predicate of a field is called with
+/// the value that contains the field, and the field itself: `fn(&Self, &FieldType) -> bool`.
+/// The predicate of a variant is only called with the value: `fn(&Self) -> bool`. Predicates of the fields
+/// are evaluated before the visitor is driven through any of them:
+///
+/// ```ignore
+/// #[derive(Drive, DriveMut)]
+/// struct Function {
+///     name: Ident,
+///     #[drive(skip_if = "Function::is_extern")]
+///     body: Block,
+///     #[drive(skip)]
+///     is_extern: bool,
+/// }
+///
+/// impl Function {
+///     fn is_extern(&self, _body: &Block) -> bool {
+///         self.is_extern
+///     }
+/// }
+/// ```
+///
+/// ### `#[drive(order = N)]`
+///
+/// If applied to a field, changes the position of the field in the traversal without moving it in the
+/// type's layout. Fields are driven through in ascending `order`, which is `0` by default, and fields with
+/// the same `order` keep their declaration order:
+///
+/// ```ignore
+/// #[derive(Drive)]
+/// struct Assign {
+///     // The value is evaluated before the target
+///     #[drive(order = 1)]
+///     target: Place,
+///     value: Expr,
+/// }
+/// ```
+///
+/// ### `#[drive(reverse)]`
+///
+/// If applied to a struct or an enum, the derived implementation drives the visitor through the fields
+/// in the reverse order, i.e. in descending `order`, starting from the last declared field.
+///
+/// ### `#[drive(bound = "...")]`
+///
+/// The derived implementation of a generic type requires every type parameter to be `'static`,
+/// and every type parameter occurring in the type of a field the visitor is driven through to implement
+/// [`Drive`] (or [`DriveMut`]). If the field's type refers to an associated type of a type parameter instead,
+/// e.g. `Vec<L::Item>`, the associated type has to implement `Drive`, rather than the type parameter.
+/// Fields that are skipped or driven through with a custom function don't add any bounds.
+///
+/// If applied to a struct or an enum, replaces the inferred `Drive` / `DriveMut` bounds with the given
+/// where clause predicates, e.g. when the inferred bounds aren't enough to drive the visitor through a field.
+/// Use `#[drive(bound(drive = "...", drive_mut = "..."))]` to give different bounds to each derived
+/// implementation:
+///
+/// ```ignore
+/// #[derive(Drive, DriveMut)]
+/// #[drive(bound(drive = "T: Drive", drive_mut = "T: DriveMut + Unshare"))]
+/// struct Cached<T> {
+///     value: Rc<T>,
+/// }
+/// ```
+///
+/// ### `#[drive(unshare = "clone" | "skip" | "panic")]`
+///
+/// If applied to a struct or an enum, the derived implementation of [`DriveMut`] also implements [`Unshare`]
+/// for the type, so that `Arc<Self>` and `Rc<Self>` implement `DriveMut` as well. See [`Unshare`] for the
+/// meaning of every policy.
+///
+/// ### `#[drive(rebuild)]`
+///
+/// If applied to a field, the derived implementation of [`DriveMut`] drives the visitor through it with
+/// [`DriveRebuild`], so that map keys and set elements can be mutated as well. The derived implementation
+/// of `Drive` is unaffected.
+///
+/// ### `#[drive(entries)]`
+///
+/// If applied to a map field, the derived implementation drives the visitor through every key-value pair
+/// as a single [`MapEntry`], see [`drive_entries`].
+///
+/// ### `#[drive(leaf)]`
+///
+/// If applied to a field, the derived implementation makes the visitor enter and exit the field's value
+/// without driving it through any children, see [`drive_leaf`]. The field's type only has to be `'static`,
+/// so visitors can observe e.g. `String` or foreign types without the `std-types-drive` feature or a newtype:
+///
+/// ```ignore
+/// #[derive(Drive)]
+/// struct File {
+///     #[drive(leaf)]
+///     name: String,
+///     #[drive(leaf)]
+///     size: u64,
+/// }
+/// ```
+///
+/// ### `#[drive(upgrade)]`
+///
+/// `Weak` pointers don't drive the visitor through anything, since they usually point back to a parent,
+/// and following them would make the traversal run forever. If applied to a `Weak` field, the derived
+/// implementation of `Drive` upgrades the pointer and drives the visitor through its value, see
+/// [`drive_upgraded`]. The derived implementation of [`DriveMut`] is unaffected.
 ///
 /// ### `#[drive(with="path")]`
 ///
 /// Drive a visitor through a field using a custom function.
-/// The function must have the following signature: `fn<V: Visitor>(&T, &mut V)`.
+/// The function must have the following signature: `fn<V: Visitor>(&T, &mut V) -> ControlFlow<V::Break>`.
 ///
 /// In the example below, this attribute is used to customize driving through a [Vec]:
 ///
@@ -451,154 +1483,947 @@ pub enum Event {
 ///     chapters: Vec<Chapter>,
 /// }
 ///
-/// fn reverse_vec_driver<T, V: Visitor>(vec: &Vec<T>, visitor: &mut V) {
+/// fn reverse_vec_driver<T, V: Visitor>(vec: &Vec<T>, visitor: &mut V) -> ControlFlow<V::Break> {
 ///     for item in vec.iter().rev() {
-///         item.drive(visitor);
+///         item.drive(visitor)?;
 ///     }
+///     ControlFlow::Continue(())
+/// }
+/// ```
+///
+/// If applied to an enum variant, the function is called with references to all the fields of the variant,
+/// in declaration order, followed by the visitor: `fn<V: Visitor>(&A, &B, &mut V) -> ControlFlow<V::Break>`.
+///
+/// If applied to a struct or an enum itself, the function replaces the traversal of the type's fields / variants,
+/// and is called with `&Self` and the visitor. Unless the type is also marked with `#[drive(skip)]`, the visitor
+/// still enters and exits the type itself around the call.
+///
+/// For [`DriveMut`], the function receives mutable references and a [`VisitorMut`] instead.
+///
+/// ### `#[drive(with_ref="path")]`, `#[drive(with_mut="path")]`
+///
+/// Like `#[drive(with="path")]`, but only used by the derived implementation of [`Drive`] / [`DriveMut`]
+/// respectively, so that a type can derive both traits with different custom functions.
+/// The other derived implementation drives the visitor as usual, unless it is customized as well:
+///
+/// ```ignore
+/// #[derive(Drive, DriveMut)]
+/// struct Book {
+///     #[drive(with_ref="reverse_vec_driver", with_mut="reverse_vec_driver_mut")]
+///     chapters: Vec<Chapter>,
+/// }
+/// ```
+///
+/// ### `#[drive(children = "path")]`
+///
+/// If applied to a struct or an enum, the derived implementation also drives the visitor through the value
+/// returned by the given method, after the type's fields / variants and before exiting the type itself.
+/// This way children that are kept in private storage or computed on demand can be visited as well.
+/// The method is called with `&Self` and may return anything implementing [`VirtualChildren`], i.e.
+/// a value implementing `Drive` or something iterating over references to such values:
+///
+/// ```ignore
+/// #[derive(Drive)]
+/// #[drive(children = "Function::body")]
+/// struct Function {
+///     name: Ident,
+///     #[drive(skip)]
+///     source: String,
+/// }
+///
+/// impl Function {
+///     fn body(&self) -> Block {
+///         parse_block(&self.source)
+///     }
+/// }
+/// ```
+///
+/// Like `with`, it has `children_ref` and `children_mut` counterparts that only apply to the derived
+/// implementation of `Drive` / `DriveMut`. For `DriveMut`, the method is called with `&mut Self` and may return
+/// anything implementing [`VirtualChildrenMut`].
+///
+/// ### `#[drive(on_enter = "path", on_exit = "path")]`
+///
+/// If applied to a struct or an enum, the derived implementation calls the given functions when the traversal
+/// enters / leaves the type, before the visitor enters it and after the visitor exits it, respectively.
+/// They have the same signature as a type-level `with` function: `fn<V: Visitor>(&Self, &mut V) -> ControlFlow<V::Break>`.
+/// Unless `on_enter` breaks the traversal, `on_exit` is called even if the traversal is interrupted, so that
+/// scoping can be implemented once in the data model instead of in every visitor:
+///
+/// ```ignore
+/// // Visitors are notified of a scope around every block
+/// #[derive(Drive)]
+/// #[drive(on_enter = "Scope::open", on_exit = "Scope::close")]
+/// struct Block {
+///     statements: Vec<Statement>,
+/// }
+///
+/// struct Scope;
+///
+/// impl Scope {
+///     fn open<V: Visitor>(_block: &Block, visitor: &mut V) -> ControlFlow<V::Break> {
+///         visitor.visit(&Scope, Event::Enter)?;
+///         ControlFlow::Continue(())
+///     }
+///
+///     fn close<V: Visitor>(_block: &Block, visitor: &mut V) -> ControlFlow<V::Break> {
+///         visitor.visit(&Scope, Event::Exit)?;
+///         ControlFlow::Continue(())
+///     }
+/// }
+/// ```
+///
+/// Both have `_ref` and `_mut` counterparts, see `#[drive(with_ref="path")]`.
+///
+/// ### `#[drive(crate = "path")]`
+///
+/// If applied to a struct or an enum, the derived implementation refers to this crate by the given path
+/// instead of `::derive_visitor`, so that the derive can be used through a re-export:
+///
+/// ```ignore
+/// use my_framework::visitor::Drive;
+///
+/// #[derive(Drive)]
+/// #[drive(crate = "my_framework::visitor")]
+/// struct Directory {
+///     items: Vec<DirectoryItem>,
 /// }
 /// ```
 pub trait Drive: Any {
-    fn drive<V: Visitor>(&self, visitor: &mut V);
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break>;
+
+    /// Drives a visitor through this data structure, given the items enclosing it, see
+    /// [Ancestors](Visitor#ancestors).
+    ///
+    /// Calls [`drive`](Drive::drive) by default, so the items of an implementation that doesn't override this
+    /// method don't know their ancestors.
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        _ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        self.drive(visitor)
+    }
+
+    /// Drive a visitor chosen at runtime through this data structure.
+    ///
+    /// Equivalent to [`drive`](Drive::drive), but doesn't require the type of the visitor to be known
+    /// at compile time.
+    fn drive_dyn<B>(&self, mut visitor: &mut dyn Visitor<Break = B>) -> ControlFlow<B> {
+        self.drive(&mut visitor)
+    }
+
+    /// Drive a [`TryVisitor`] through this data structure, stopping at the first error.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error returned by the visitor.
+    fn try_drive<V: TryVisitor>(&self, visitor: &mut V) -> Result<(), V::Error> {
+        into_result(self.drive(&mut TryVisitorAdapter(visitor)))
+    }
+}
+
+/// Drive a [`VisitorMut`] over this datastructure.
+///
+/// This is equivalent to [`Drive`], but gives the possibility to mutate the datastructure as it is visited.
+///
+/// ## Example
+///
+/// ```rust
+/// use derive_visitor::{DriveMut, Event, visitor_fn_mut};
+///
+/// #[derive(DriveMut, Default)]
+/// struct Node{ children: Vec<Node> }
+///
+/// let mut node = Node{children: vec![Node::default(), Node::default()]};
+///
+/// let _ = node.drive_mut(&mut visitor_fn_mut(|n: &mut Node, event|
+///     // Mutate the element on exit so that we are not driven to the newly created elements
+///     if let Event::Exit = event {
+///         n.children.resize_with(3, Default::default);
+///     }
+/// ));
+///
+/// // We have driven over all the initial nodes...
+/// assert_eq!(node.children.len(), 3);
+/// assert_eq!(node.children[0].children.len(), 3);
+/// assert_eq!(node.children[1].children.len(), 3);
+/// // ... but not over the newly created ones
+/// assert_eq!(node.children[2].children.len(), 0);
+/// ```
+pub trait DriveMut: Any {
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break>;
+
+    /// Equivalent of [`Drive::drive_dyn`] for [`VisitorMut`].
+    fn drive_dyn_mut<B>(&mut self, mut visitor: &mut dyn VisitorMut<Break = B>) -> ControlFlow<B> {
+        self.drive_mut(&mut visitor)
+    }
+
+    /// Drive a [`TryVisitorMut`] through this data structure, stopping at the first error.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error returned by the visitor.
+    fn try_drive_mut<V: TryVisitorMut>(&mut self, visitor: &mut V) -> Result<(), V::Error> {
+        into_result(self.drive_mut(&mut TryVisitorAdapter(visitor)))
+    }
+}
+
+// Lets `TryVisitor`s and `TryVisitorMut`s be driven as regular visitors, breaking with their errors
+struct TryVisitorAdapter<'a, V: ?Sized>(&'a mut V);
+
+impl<V: TryVisitor + ?Sized> Visitor for TryVisitorAdapter<'_, V> {
+    type Break = V::Error;
+
+    fn visit(&mut self, item: &dyn Any, event: Event) -> ControlFlow<V::Error, Children> {
+        match self.0.visit(item, event) {
+            Ok(()) => ControlFlow::Continue(Children::Visit),
+            Err(error) => ControlFlow::Break(error),
+        }
+    }
+
+    fn poisoned(&mut self) -> ControlFlow<V::Error, PoisonPolicy> {
+        match self.0.poisoned() {
+            Ok(policy) => ControlFlow::Continue(policy),
+            Err(error) => ControlFlow::Break(error),
+        }
+    }
+}
+
+impl<V: TryVisitorMut + ?Sized> VisitorMut for TryVisitorAdapter<'_, V> {
+    type Break = V::Error;
+
+    fn visit(&mut self, item: &mut dyn Any, event: Event) -> ControlFlow<V::Error, Children> {
+        match self.0.visit(item, event) {
+            Ok(()) => ControlFlow::Continue(Children::Visit),
+            Err(error) => ControlFlow::Break(error),
+        }
+    }
+
+    fn poisoned(&mut self) -> ControlFlow<V::Error, PoisonPolicy> {
+        match self.0.poisoned() {
+            Ok(policy) => ControlFlow::Continue(policy),
+            Err(error) => ControlFlow::Break(error),
+        }
+    }
+}
+
+fn into_result<E>(flow: ControlFlow<E>) -> Result<(), E> {
+    match flow {
+        ControlFlow::Continue(()) => Ok(()),
+        ControlFlow::Break(error) => Err(error),
+    }
+}
+
+/// Drives a visitor through a node using the `drive` closure, surrounding it with
+/// [`push_node`](Visitor::push_node) and [`pop_node`](Visitor::pop_node) notifications.
+///
+/// [`pop_node`](Visitor::pop_node) is called even if the traversal is interrupted.
+/// The derived [`Drive`] implementations are built on top of this function,
+/// see [Implementing manually](Drive#implementing-manually).
+pub fn drive_node<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    drive: impl FnOnce(&mut V) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    visitor.push_node();
+    let flow = drive(visitor);
+    visitor.pop_node();
+    flow
+}
+
+/// Equivalent of [`drive_node`] for [`VisitorMut`].
+pub fn drive_node_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    drive: impl FnOnce(&mut V) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    visitor.push_node();
+    let flow = drive(visitor);
+    visitor.pop_node();
+    flow
+}
+
+/// Drives a visitor through the value of a shared pointer using the `drive` closure, unless
+/// [`enter_shared`](Visitor::enter_shared) tells to skip it.
+///
+/// [`exit_shared`](Visitor::exit_shared) is called even if the value is skipped or the traversal is interrupted.
+pub fn drive_shared<V: Visitor + ?Sized>(
+    allocation: Allocation,
+    visitor: &mut V,
+    drive: impl FnOnce(&mut V) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    let flow = match visitor.enter_shared(allocation) {
+        Children::Visit => drive(visitor),
+        Children::Skip => ControlFlow::Continue(()),
+    };
+    visitor.exit_shared();
+    flow
+}
+
+/// Equivalent of [`drive_shared`] for [`VisitorMut`].
+pub fn drive_shared_mut<V: VisitorMut + ?Sized>(
+    allocation: Allocation,
+    visitor: &mut V,
+    drive: impl FnOnce(&mut V) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    let flow = match visitor.enter_shared(allocation) {
+        Children::Visit => drive(visitor),
+        Children::Skip => ControlFlow::Continue(()),
+    };
+    visitor.exit_shared();
+    flow
+}
+
+/// Drives a visitor through `item` as a leaf, i.e. makes it enter and exit `item` without driving it
+/// through any children. `item` doesn't have to implement [`Drive`].
+///
+/// The derived [`Drive`] implementation uses this function for fields marked with `#[drive(leaf)]`.
+pub fn drive_leaf<T: Any, V: Visitor + ?Sized>(
+    item: &T,
+    visitor: &mut V,
+    ancestors: Ancestors<'_>,
+) -> ControlFlow<V::Break> {
+    drive_node(visitor, |visitor| {
+        visitor.visit_with_ancestors(item, Event::Enter, ancestors)?;
+        visitor.visit_with_ancestors(item, Event::Exit, ancestors)?;
+        ControlFlow::Continue(())
+    })
+}
+
+/// Equivalent of [`drive_leaf`] for [`VisitorMut`], which doesn't receive the ancestors.
+pub fn drive_leaf_mut<T: Any, V: VisitorMut + ?Sized>(
+    item: &mut T,
+    visitor: &mut V,
+) -> ControlFlow<V::Break> {
+    drive_node_mut(visitor, |visitor| {
+        visitor.visit(item, Event::Enter)?;
+        visitor.visit(item, Event::Exit)?;
+        ControlFlow::Continue(())
+    })
+}
+
+// Drives a visitor through a child, keeping path segments balanced even if the traversal is interrupted
+fn drive_segment<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    segment: PathSegment<'_>,
+    drive: impl FnOnce(&mut V) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    visitor.push_path_segment(segment);
+    let flow = drive(visitor);
+    visitor.pop_path_segment();
+    flow
+}
+
+fn drive_segment_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    segment: PathSegment<'_>,
+    drive: impl FnOnce(&mut V) -> ControlFlow<V::Break>,
+) -> ControlFlow<V::Break> {
+    visitor.push_path_segment(segment);
+    let flow = drive(visitor);
+    visitor.pop_path_segment();
+    flow
+}
+
+// Helper traits to the generic `IntoIterator` Drive impls and to `VirtualChildren`. They are public so that
+// they can bound public impls, but sealed in a private module, so they can't be named outside of this crate
+mod deref_and_drive {
+    use super::{Ancestors, ControlFlow, Visitor, VisitorMut};
+
+    pub trait DerefAndDrive {
+        fn deref_and_drive<V: Visitor>(
+            self,
+            index: usize,
+            visitor: &mut V,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<V::Break>;
+    }
+
+    // Drives a VisitorMut over a mutable reference
+    pub trait DerefAndDriveMut {
+        fn deref_and_drive_mut<V: VisitorMut>(
+            self,
+            index: usize,
+            visitor: &mut V,
+        ) -> ControlFlow<V::Break>;
+    }
+}
+
+use deref_and_drive::{DerefAndDrive, DerefAndDriveMut};
+
+// Most collections iterate over item references, this is the trait impl that handles that case
+impl<T: Drive> DerefAndDrive for &T {
+    fn deref_and_drive<V: Visitor>(
+        self,
+        index: usize,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        drive_segment(visitor, PathSegment::Index(index), |visitor| {
+            self.drive_with_ancestors(visitor, ancestors)
+        })
+    }
+}
+
+impl<T: DriveMut> DerefAndDriveMut for &mut T {
+    fn deref_and_drive_mut<V: VisitorMut>(
+        self,
+        index: usize,
+        visitor: &mut V,
+    ) -> ControlFlow<V::Break> {
+        drive_segment_mut(visitor, PathSegment::Index(index), |visitor| {
+            self.drive_mut(visitor)
+        })
+    }
+}
+
+// Map-like collections iterate over item references pairs
+impl<TK: Drive, TV: Drive> DerefAndDrive for (&TK, &TV) {
+    fn deref_and_drive<V: Visitor>(
+        self,
+        _index: usize,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        drive_segment(visitor, PathSegment::Key(self.0), |visitor| {
+            self.0.drive_with_ancestors(visitor, ancestors)?;
+            self.1.drive_with_ancestors(visitor, ancestors)
+        })
+    }
+}
+
+// Map-like collections have mutable iterators that allow mutating only the value, not the key
+impl<TK: Any, TV: DriveMut> DerefAndDriveMut for (&TK, &mut TV) {
+    fn deref_and_drive_mut<V: VisitorMut>(
+        self,
+        _index: usize,
+        visitor: &mut V,
+    ) -> ControlFlow<V::Break> {
+        drive_segment_mut(visitor, PathSegment::Key(self.0), |visitor| {
+            self.1.drive_mut(visitor)
+        })
+    }
+}
+
+// Implement Drive and DriveMut for container types in standard library.
+macro_rules! impl_drive_for_into_iterator {
+    ( $type:ty ; $kind:ident ; $($generics:tt)+ ) => {
+        impl< $($generics)+ > Drive for $type
+        where
+            $type: 'static,
+            for<'a> &'a $type: IntoIterator,
+            for<'a> <&'a $type as IntoIterator>::IntoIter: ExactSizeIterator,
+            for<'a> <&'a $type as IntoIterator>::Item: DerefAndDrive,
+        {
+            fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+                self.drive_with_ancestors(visitor, Ancestors::new())
+            }
+
+            fn drive_with_ancestors<V: Visitor>(
+                &self,
+                visitor: &mut V,
+                ancestors: Ancestors<'_>,
+            ) -> ControlFlow<V::Break> {
+                drive_container(
+                    visitor,
+                    ContainerKind::$kind,
+                    self.into_iter(),
+                    |visitor, index, item| item.deref_and_drive(index, visitor, ancestors),
+                )
+            }
+        }
+
+        impl< $($generics)+ > DriveMut for $type
+        where
+            $type: 'static,
+            for<'a> &'a mut $type: IntoIterator,
+            for<'a> <&'a mut $type as IntoIterator>::IntoIter: ExactSizeIterator,
+            for<'a> <&'a mut $type as IntoIterator>::Item: DerefAndDriveMut,
+        {
+            fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+                drive_container_mut(
+                    visitor,
+                    ContainerKind::$kind,
+                    self.into_iter(),
+                    |visitor, index, item| item.deref_and_drive_mut(index, visitor),
+                )
+            }
+        }
+    };
+}
+
+impl_drive_for_into_iterator! { [T] ; Sequence ; T }
+impl_drive_for_into_iterator! { Vec<T> ; Sequence ; T }
+impl_drive_for_into_iterator! { std::collections::BTreeSet<T> ; Set ; T }
+impl_drive_for_into_iterator! { std::collections::BinaryHeap<T> ; Set ; T }
+impl_drive_for_into_iterator! { std::collections::HashSet<T> ; Set ; T }
+impl_drive_for_into_iterator! { std::collections::LinkedList<T> ; Sequence ; T }
+impl_drive_for_into_iterator! { std::collections::VecDeque<T> ; Sequence ; T }
+impl_drive_for_into_iterator! { std::collections::BTreeMap<T, U> ; Map ; T, U }
+impl_drive_for_into_iterator! { std::collections::HashMap<T, U> ; Map ; T, U }
+impl_drive_for_into_iterator! { [T; N] ; Sequence ; T, const N: usize }
+
+/// Mutable traversal of collections whose elements can't be mutated in place, such as map keys
+/// and set elements.
+///
+/// The elements are taken out of the collection, the visitor is driven through every one of them,
+/// and they are inserted back. All elements are inserted back even if the traversal is interrupted.
+///
+/// If several elements of a set, or keys of a map, become equal, only the first one in the original
+/// iteration order is kept (along with its value), and the rest are dropped. A [`BinaryHeap`](std::collections::BinaryHeap)
+/// keeps equal elements, so none of its elements are dropped.
+///
+/// While driving the visitor through a map key, the path segment is the position of the entry,
+/// since the key itself is being mutated. The value is reported under the mutated key.
+///
+/// A field of a derived [`DriveMut`] implementation uses this trait instead of `DriveMut` when marked with
+/// `#[drive(rebuild)]`:
+///
+/// ```rust
+/// use derive_visitor::{visitor_enter_fn_mut, DriveMut};
+/// use std::collections::BTreeSet;
+///
+/// #[derive(DriveMut, PartialEq, Eq, PartialOrd, Ord, Debug)]
+/// struct Tag(#[drive(skip)] String);
+///
+/// #[derive(DriveMut)]
+/// struct Post {
+///     #[drive(rebuild)]
+///     tags: BTreeSet<Tag>,
+/// }
+///
+/// let mut post = Post {
+///     tags: ["Rust", "rust", "Visitor"].into_iter().map(|tag| Tag(tag.to_string())).collect(),
+/// };
+/// let _ = post.drive_mut(&mut visitor_enter_fn_mut(|tag: &mut Tag| tag.0.make_ascii_lowercase()));
+///
+/// let tags: Vec<_> = post.tags.iter().map(|tag| tag.0.as_str()).collect();
+/// assert_eq!(tags, ["rust", "visitor"]);
+/// ```
+pub trait DriveRebuild {
+    fn drive_rebuild<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break>;
+}
+
+// Drives a visitor through the elements taken out of a collection, visiting none of them after a break
+fn drive_taken<T: DriveMut, V: VisitorMut>(
+    items: &mut [T],
+    visitor: &mut V,
+) -> ControlFlow<V::Break> {
+    drive_container_mut(
+        visitor,
+        ContainerKind::Set,
+        items.iter_mut(),
+        |visitor, index, item| item.deref_and_drive_mut(index, visitor),
+    )
+}
+
+fn drive_taken_entries<K: DriveMut, T: DriveMut, V: VisitorMut>(
+    entries: &mut [(K, T)],
+    visitor: &mut V,
+) -> ControlFlow<V::Break> {
+    drive_container_mut(
+        visitor,
+        ContainerKind::Map,
+        entries.iter_mut(),
+        |visitor, index, (key, value)| {
+            drive_segment_mut(visitor, PathSegment::Index(index), |visitor| {
+                key.drive_mut(visitor)
+            })?;
+            drive_segment_mut(visitor, PathSegment::Key(key), |visitor| {
+                value.drive_mut(visitor)
+            })
+        },
+    )
+}
+
+impl<T, S> DriveRebuild for std::collections::HashSet<T, S>
+where
+    T: DriveMut + Eq + std::hash::Hash,
+    S: std::hash::BuildHasher,
+{
+    fn drive_rebuild<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let mut items: Vec<T> = self.drain().collect();
+        let flow = drive_taken(&mut items, visitor);
+        self.extend(items);
+        flow
+    }
+}
+
+impl<T> DriveRebuild for std::collections::BTreeSet<T>
+where
+    T: DriveMut + Ord,
+{
+    fn drive_rebuild<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let mut items: Vec<T> = std::mem::take(self).into_iter().collect();
+        let flow = drive_taken(&mut items, visitor);
+        self.extend(items);
+        flow
+    }
+}
+
+impl<T> DriveRebuild for std::collections::BinaryHeap<T>
+where
+    T: DriveMut + Ord,
+{
+    fn drive_rebuild<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let mut items = std::mem::take(self).into_vec();
+        let flow = drive_taken(&mut items, visitor);
+        *self = items.into();
+        flow
+    }
+}
+
+impl<K, T, S> DriveRebuild for std::collections::HashMap<K, T, S>
+where
+    K: DriveMut + Eq + std::hash::Hash,
+    T: DriveMut,
+    S: std::hash::BuildHasher,
+{
+    fn drive_rebuild<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let mut entries: Vec<(K, T)> = self.drain().collect();
+        let flow = drive_taken_entries(&mut entries, visitor);
+        for (key, value) in entries {
+            self.entry(key).or_insert(value);
+        }
+        flow
+    }
+}
+
+impl<K, T> DriveRebuild for std::collections::BTreeMap<K, T>
+where
+    K: DriveMut + Ord,
+    T: DriveMut,
+{
+    fn drive_rebuild<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let mut entries: Vec<(K, T)> = std::mem::take(self).into_iter().collect();
+        let flow = drive_taken_entries(&mut entries, visitor);
+        for (key, value) in entries {
+            self.entry(key).or_insert(value);
+        }
+        flow
+    }
+}
+
+// Option and Result are not sequences, so they are transparent in the path
+impl<T> Drive for Option<T>
+where
+    T: Drive,
+{
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        drive_container(
+            visitor,
+            ContainerKind::Option,
+            self.iter(),
+            |visitor, _, value| value.drive_with_ancestors(visitor, ancestors),
+        )
+    }
+}
+
+impl<T> DriveMut for Option<T>
+where
+    T: DriveMut,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        drive_container_mut(
+            visitor,
+            ContainerKind::Option,
+            self.iter_mut(),
+            |visitor, _, value| value.drive_mut(visitor),
+        )
+    }
+}
+
+impl<T, U> Drive for Result<T, U>
+where
+    T: Drive,
+    U: 'static,
+{
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        drive_container(
+            visitor,
+            ContainerKind::Result,
+            self.iter(),
+            |visitor, _, value| value.drive_with_ancestors(visitor, ancestors),
+        )
+    }
+}
+
+impl<T, U> DriveMut for Result<T, U>
+where
+    T: DriveMut,
+    U: 'static,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        drive_container_mut(
+            visitor,
+            ContainerKind::Result,
+            self.iter_mut(),
+            |visitor, _, value| value.drive_mut(visitor),
+        )
+    }
+}
+
+impl<T> Drive for Box<T>
+where
+    T: Drive + ?Sized,
+{
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        (**self).drive_with_ancestors(visitor, ancestors)
+    }
+}
+
+impl<T> DriveMut for Box<T>
+where
+    T: DriveMut + ?Sized,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        (**self).drive_mut(visitor)
+    }
+}
+
+impl<T> Drive for Arc<T>
+where
+    T: Drive + ?Sized,
+{
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        drive_shared(Allocation::of(Arc::as_ptr(self)), visitor, |visitor| {
+            (**self).drive_with_ancestors(visitor, ancestors)
+        })
+    }
 }
 
-/// Drive a [`VisitorMut`] over this datastructure.
+/// Opts `Arc<Self>` and `Rc<Self>` into [`DriveMut`].
 ///
-/// This is equivalent to [`Drive`], but gives the possibility to mutate the datastructure as it is visited.
+/// A [`VisitorMut`] is driven through the value of an `Arc` or an `Rc` directly if the pointer is the only one to
+/// its value, like with [`Arc::get_mut`]. If the value is shared with other pointers (including weak ones),
+/// [`unshare`](Unshare::unshare) decides what happens.
 ///
-/// ## Example
+/// This trait can be implemented by hand or with the `unshare` parameter of the derived [`DriveMut`]:
 ///
-/// ```rust
-/// use derive_visitor::{DriveMut, Event, visitor_fn_mut};
+/// - `#[drive(unshare = "clone")]` clones the value into a new allocation and visits the clone, leaving
+///   the other pointers untouched, like [`Arc::make_mut`] does. Requires the type to implement [`Clone`];
+/// - `#[drive(unshare = "skip")]` doesn't drive the visitor through shared values;
+/// - `#[drive(unshare = "panic")]` panics.
 ///
-/// #[derive(DriveMut, Default)]
-/// struct Node{ children: Vec<Node> }
+/// ```rust
+/// use derive_visitor::{visitor_enter_fn_mut, DriveMut};
+/// use std::sync::Arc;
 ///
-/// let mut node = Node{children: vec![Node::default(), Node::default()]};
+/// #[derive(DriveMut, Clone)]
+/// #[drive(unshare = "clone")]
+/// struct Expr {
+///     #[drive(skip)]
+///     value: u32,
+///     operands: Vec<Arc<Expr>>,
+/// }
 ///
-/// node.drive_mut(&mut visitor_fn_mut(|n: &mut Node, event|
-///     // Mutate the element on exit so that we are not driven to the newly created elements
-///     if let Event::Exit = event {
-///         n.children.resize_with(3, Default::default);
-///     }
-/// ));
+/// let leaf = Arc::new(Expr { value: 1, operands: vec![] });
+/// let mut sum = Expr { value: 2, operands: vec![leaf.clone(), leaf.clone()] };
 ///
-/// // We have driven over all the initial nodes...
-/// assert_eq!(node.children.len(), 3);
-/// assert_eq!(node.children[0].children.len(), 3);
-/// assert_eq!(node.children[1].children.len(), 3);
-/// // ... but not over the newly created ones
-/// assert_eq!(node.children[2].children.len(), 0);
+/// let _ = sum.drive_mut(&mut visitor_enter_fn_mut(|expr: &mut Expr| expr.value *= 10));
+/// assert_eq!(sum.operands[0].value, 10);
+/// assert_eq!(sum.operands[1].value, 10);
+/// assert_eq!(leaf.value, 1);
 /// ```
-pub trait DriveMut: Any {
-    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V);
+pub trait Unshare: Sized {
+    /// Called when a [`VisitorMut`] reaches a shared value. Returning a new value replaces the pointer with
+    /// a pointer to that value, which is then visited; returning `None` skips the value.
+    fn unshare(&self) -> Option<Self>;
 }
 
-// Helper trait to the generic `IntoIterator` Drive impl
-trait DerefAndDrive {
-    fn deref_and_drive<V: Visitor>(self, visitor: &mut V);
+impl<T> DriveMut for Arc<T>
+where
+    T: DriveMut + Unshare,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        if Arc::get_mut(self).is_none() {
+            match self.unshare() {
+                Some(value) => *self = Arc::new(value),
+                None => return ControlFlow::Continue(()),
+            }
+        }
+        let allocation = Allocation::of(Arc::as_ptr(self));
+        drive_shared_mut(allocation, visitor, |visitor| {
+            Arc::get_mut(self).map_or(ControlFlow::Continue(()), |value| value.drive_mut(visitor))
+        })
+    }
 }
 
-// Drives a VisitorMut over a mutable reference
-trait DerefAndDriveMut {
-    fn deref_and_drive_mut<V: VisitorMut>(self, visitor: &mut V);
+impl<T> DriveMut for Rc<T>
+where
+    T: DriveMut + Unshare,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        if Rc::get_mut(self).is_none() {
+            match self.unshare() {
+                Some(value) => *self = Rc::new(value),
+                None => return ControlFlow::Continue(()),
+            }
+        }
+        let allocation = Allocation::of(Rc::as_ptr(self));
+        drive_shared_mut(allocation, visitor, |visitor| {
+            Rc::get_mut(self).map_or(ControlFlow::Continue(()), |value| value.drive_mut(visitor))
+        })
+    }
 }
 
-// Most collections iterate over item references, this is the trait impl that handles that case
-impl<T: Drive> DerefAndDrive for &T {
-    fn deref_and_drive<V: Visitor>(self, visitor: &mut V) {
-        self.drive(visitor);
+impl<T> Drive for Rc<T>
+where
+    T: Drive + ?Sized,
+{
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        drive_shared(Allocation::of(Rc::as_ptr(self)), visitor, |visitor| {
+            (**self).drive_with_ancestors(visitor, ancestors)
+        })
     }
 }
 
-impl<T: DriveMut> DerefAndDriveMut for &mut T {
-    fn deref_and_drive_mut<V: VisitorMut>(self, visitor: &mut V) {
-        self.drive_mut(visitor);
+// Weak references are usually back references, e.g. to a parent, so they aren't driven through by default,
+// see `drive_upgraded`
+
+impl<T: ?Sized + 'static> Drive for rc::Weak<T> {
+    fn drive<V: Visitor>(&self, _visitor: &mut V) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
     }
 }
 
-// Map-like collections iterate over item references pairs
-impl<TK: Drive, TV: Drive> DerefAndDrive for (&TK, &TV) {
-    fn deref_and_drive<V: Visitor>(self, visitor: &mut V) {
-        self.0.drive(visitor);
-        self.1.drive(visitor);
+impl<T: ?Sized + 'static> Drive for sync::Weak<T> {
+    fn drive<V: Visitor>(&self, _visitor: &mut V) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
     }
 }
 
-// Map-like collections have mutable iterators that allow mutating only the value, not the key
-impl<TK, TV: DriveMut> DerefAndDriveMut for (TK, &mut TV) {
-    fn deref_and_drive_mut<V: VisitorMut>(self, visitor: &mut V) {
-        self.1.drive_mut(visitor);
+/// A weak pointer that can be upgraded to a strong one, see [`drive_upgraded`].
+pub trait Upgrade {
+    type Strong: Drive;
+
+    fn upgrade(&self) -> Option<Self::Strong>;
+}
+
+impl<T: Drive + ?Sized> Upgrade for rc::Weak<T> {
+    type Strong = Rc<T>;
+
+    fn upgrade(&self) -> Option<Rc<T>> {
+        rc::Weak::upgrade(self)
     }
 }
 
-// Implement Drive and DriveMut for container types in standard library.
-macro_rules! impl_drive_for_into_iterator {
-    ( $type:ty ; $($generics:tt)+ ) => {
-        impl< $($generics)+ > Drive for $type
-        where
-            $type: 'static,
-            for<'a> &'a $type: IntoIterator,
-            for<'a> <&'a $type as IntoIterator>::Item: DerefAndDrive,
-        {
-            fn drive<V: Visitor>(&self, visitor: &mut V) {
-                for item in self {
-                    item.deref_and_drive(visitor);
-                }
-            }
-        }
+impl<T: Drive + ?Sized> Upgrade for sync::Weak<T> {
+    type Strong = Arc<T>;
 
-        impl< $($generics)+ > DriveMut for $type
-        where
-            $type: 'static,
-            for<'a> &'a mut $type: IntoIterator,
-            for<'a> <&'a mut $type as IntoIterator>::Item: DerefAndDriveMut,
-        {
-            fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-                for item in self {
-                    item.deref_and_drive_mut(visitor);
-                }
-            }
-        }
-    };
+    fn upgrade(&self) -> Option<Arc<T>> {
+        sync::Weak::upgrade(self)
+    }
 }
 
-impl_drive_for_into_iterator! { [T] ; T }
-impl_drive_for_into_iterator! { Vec<T> ; T }
-impl_drive_for_into_iterator! { std::collections::BTreeSet<T> ; T }
-impl_drive_for_into_iterator! { std::collections::BinaryHeap<T> ; T }
-impl_drive_for_into_iterator! { std::collections::HashSet<T> ; T }
-impl_drive_for_into_iterator! { std::collections::LinkedList<T> ; T }
-impl_drive_for_into_iterator! { std::collections::VecDeque<T> ; T }
-impl_drive_for_into_iterator! { Option<T> ; T }
-impl_drive_for_into_iterator! { Result<T, U> ; T, U }
-impl_drive_for_into_iterator! { std::collections::BTreeMap<T, U> ; T, U }
-impl_drive_for_into_iterator! { std::collections::HashMap<T, U> ; T, U }
-impl_drive_for_into_iterator! { [T; N] ; T, const N: usize }
+/// Drives a visitor through the value of a weak pointer, upgrading it for the duration of the traversal.
+/// Does nothing if the value has been dropped.
+///
+/// The derived [`Drive`] implementation uses this function for fields marked with `#[drive(upgrade)]`.
+/// Since the value is driven through as an `Rc` / `Arc`, visitors keeping track of [`Allocations`]
+/// don't run into cycles.
+pub fn drive_upgraded<W: Upgrade, V: Visitor>(
+    weak: &W,
+    visitor: &mut V,
+    ancestors: Ancestors<'_>,
+) -> ControlFlow<V::Break> {
+    weak.upgrade().map_or(ControlFlow::Continue(()), |strong| {
+        strong.drive_with_ancestors(visitor, ancestors)
+    })
+}
 
-impl<T> Drive for Box<T>
+impl<T> Drive for Cow<'static, T>
 where
-    T: Drive,
+    T: Drive + ToOwned + ?Sized,
 {
-    fn drive<V: Visitor>(&self, visitor: &mut V) {
-        (**self).drive(visitor);
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        (**self).drive_with_ancestors(visitor, ancestors)
     }
 }
 
-impl<T> DriveMut for Box<T>
+// Borrowed values are cloned before being visited, as with any other mutation of a `Cow`
+impl<T> DriveMut for Cow<'static, T>
 where
-    T: DriveMut,
+    T: ToOwned + ?Sized,
+    T::Owned: DriveMut,
 {
-    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-        (**self).drive_mut(visitor);
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.to_mut().drive_mut(visitor)
     }
 }
 
-impl<T> Drive for Arc<T>
+impl<P> Drive for Pin<P>
 where
-    T: Drive,
+    P: Deref + 'static,
+    P::Target: Drive,
+{
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        (**self).drive_with_ancestors(visitor, ancestors)
+    }
+}
+
+impl<P> DriveMut for Pin<P>
+where
+    P: DerefMut + 'static,
+    P::Target: DriveMut + Unpin,
 {
-    fn drive<V: Visitor>(&self, visitor: &mut V) {
-        (**self).drive(visitor);
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.as_mut().get_mut().drive_mut(visitor)
     }
 }
 
@@ -606,9 +2431,31 @@ impl<T> Drive for Mutex<T>
 where
     T: Drive,
 {
-    fn drive<V: Visitor>(&self, visitor: &mut V) {
-        let lock = self.lock().unwrap();
-        lock.drive(visitor);
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        match unpoison(self.lock(), || visitor.poisoned())? {
+            Some(lock) => lock.drive_with_ancestors(visitor, ancestors),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+impl<T> DriveMut for Mutex<T>
+where
+    T: DriveMut,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        match unpoison(self.get_mut(), || visitor.poisoned())? {
+            Some(value) => value.drive_mut(visitor),
+            None => ControlFlow::Continue(()),
+        }
     }
 }
 
@@ -616,9 +2463,31 @@ impl<T> Drive for RwLock<T>
 where
     T: Drive,
 {
-    fn drive<V: Visitor>(&self, visitor: &mut V) {
-        let lock = self.read().unwrap();
-        lock.drive(visitor);
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        match unpoison(self.read(), || visitor.poisoned())? {
+            Some(lock) => lock.drive_with_ancestors(visitor, ancestors),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+impl<T> DriveMut for RwLock<T>
+where
+    T: DriveMut,
+{
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        match unpoison(self.get_mut(), || visitor.poisoned())? {
+            Some(value) => value.drive_mut(visitor),
+            None => ControlFlow::Continue(()),
+        }
     }
 }
 
@@ -626,9 +2495,15 @@ impl<T> DriveMut for Arc<Mutex<T>>
 where
     T: DriveMut,
 {
-    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-        let mut lock = self.lock().unwrap();
-        lock.drive_mut(visitor);
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        drive_shared_mut(
+            Allocation::of(Arc::as_ptr(self)),
+            visitor,
+            |visitor| match unpoison(self.lock(), || visitor.poisoned())? {
+                Some(mut lock) => lock.drive_mut(visitor),
+                None => ControlFlow::Continue(()),
+            },
+        )
     }
 }
 
@@ -636,9 +2511,15 @@ impl<T> DriveMut for Arc<RwLock<T>>
 where
     T: DriveMut,
 {
-    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-        let mut lock = self.write().unwrap();
-        lock.drive_mut(visitor);
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        drive_shared_mut(
+            Allocation::of(Arc::as_ptr(self)),
+            visitor,
+            |visitor| match unpoison(self.write(), || visitor.poisoned())? {
+                Some(mut lock) => lock.drive_mut(visitor),
+                None => ControlFlow::Continue(()),
+            },
+        )
     }
 }
 
@@ -646,8 +2527,16 @@ impl<T> Drive for Cell<T>
 where
     T: Drive + Copy,
 {
-    fn drive<V: Visitor>(&self, visitor: &mut V) {
-        self.get().drive(visitor);
+    fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, Ancestors::new())
+    }
+
+    fn drive_with_ancestors<V: Visitor>(
+        &self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        self.get().drive_with_ancestors(visitor, ancestors)
     }
 }
 
@@ -655,17 +2544,33 @@ impl<T> DriveMut for Cell<T>
 where
     T: DriveMut,
 {
-    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-        self.get_mut().drive_mut(visitor);
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.get_mut().drive_mut(visitor)
     }
 }
 
 impl Drive for () {
-    fn drive<V: Visitor>(&self, _visitor: &mut V) {}
+    fn drive<V: Visitor>(&self, _visitor: &mut V) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
 }
 
 impl DriveMut for () {
-    fn drive_mut<V: VisitorMut>(&mut self, _visitor: &mut V) {}
+    fn drive_mut<V: VisitorMut>(&mut self, _visitor: &mut V) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
+}
+
+impl<T: ?Sized + 'static> Drive for PhantomData<T> {
+    fn drive<V: Visitor>(&self, _visitor: &mut V) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
+}
+
+impl<T: ?Sized + 'static> DriveMut for PhantomData<T> {
+    fn drive_mut<V: VisitorMut>(&mut self, _visitor: &mut V) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
 }
 
 macro_rules! tuple_impls {
@@ -677,10 +2582,21 @@ macro_rules! tuple_impls {
                     $type: Drive
                 ),+
             {
-                fn drive<V: Visitor>(&self, visitor: &mut V) {
+                fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+                    self.drive_with_ancestors(visitor, Ancestors::new())
+                }
+
+                fn drive_with_ancestors<V: Visitor>(
+                    &self,
+                    visitor: &mut V,
+                    ancestors: Ancestors<'_>,
+                ) -> ControlFlow<V::Break> {
                     $(
-                        self.$field.drive(visitor);
+                        drive_segment(visitor, PathSegment::Field(stringify!($field)), |visitor| {
+                            self.$field.drive_with_ancestors(visitor, ancestors)
+                        })?;
                     )+
+                    ControlFlow::Continue(())
                 }
             }
 
@@ -690,10 +2606,302 @@ macro_rules! tuple_impls {
                     $type: DriveMut
                 ),+
             {
-                fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+                fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+                    $(
+                        drive_segment_mut(visitor, PathSegment::Field(stringify!($field)), |visitor| {
+                            self.$field.drive_mut(visitor)
+                        })?;
+                    )+
+                    ControlFlow::Continue(())
+                }
+            }
+
+            impl<B, $( $type ),+> Visitor for ($($type,)+)
+            where
+                $(
+                    $type: Visitor<Break = B>
+                ),+
+            {
+                type Break = B;
+
+                fn visit(&mut self, item: &dyn Any, event: Event) -> ControlFlow<B, Children> {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        if let Children::Visit = forward_children(member, Signal::from(event), || {
+                            self.$field.visit(item, event)
+                        })? {
+                            children = Children::Visit;
+                        }
+                    )+
+                    ControlFlow::Continue(children)
+                }
+
+                fn visit_with_ancestors(
+                    &mut self,
+                    item: &dyn Any,
+                    event: Event,
+                    ancestors: Ancestors<'_>,
+                ) -> ControlFlow<B, Children> {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        if let Children::Visit = forward_children(member, Signal::from(event), || {
+                            self.$field.visit_with_ancestors(item, event, ancestors)
+                        })? {
+                            children = Children::Visit;
+                        }
+                    )+
+                    ControlFlow::Continue(children)
+                }
+
+                fn visit_entry(
+                    &mut self,
+                    entry: MapEntry<'_>,
+                    event: Event,
+                    ancestors: Ancestors<'_>,
+                ) -> ControlFlow<B, Children> {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        if let Children::Visit = forward_children(member, Signal::from(event), || {
+                            self.$field.visit_entry(entry, event, ancestors)
+                        })? {
+                            children = Children::Visit;
+                        }
+                    )+
+                    ControlFlow::Continue(children)
+                }
+
+                fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.push_path_segment(segment);
+                        }
+                    )+
+                }
+
+                fn pop_path_segment(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.pop_path_segment();
+                        }
+                    )+
+                }
+
+                fn push_node(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::PushNode) {
+                            self.$field.push_node();
+                        }
+                    )+
+                }
+
+                fn pop_node(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::PopNode) {
+                            self.$field.pop_node();
+                        }
+                    )+
+                }
+
+                fn enter_shared(&mut self, allocation: Allocation) -> Children {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        let visit = || ControlFlow::<B, _>::Continue(self.$field.enter_shared(allocation));
+                        if let ControlFlow::Continue(Children::Visit) =
+                            forward_children(member, Signal::EnterShared, visit)
+                        {
+                            children = Children::Visit;
+                        }
+                    )+
+                    children
+                }
+
+                fn exit_shared(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::ExitShared) {
+                            self.$field.exit_shared();
+                        }
+                    )+
+                }
+
+                fn enter_container(&mut self, container: Container) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.enter_container(container);
+                        }
+                    )+
+                }
+
+                fn exit_container(&mut self, container: Container) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.exit_container(container);
+                        }
+                    )+
+                }
+
+                fn enter_element(&mut self, element: Element) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.enter_element(element);
+                        }
+                    )+
+                }
+
+                fn exit_element(&mut self, element: Element) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.exit_element(element);
+                        }
+                    )+
+                }
+
+                fn poisoned(&mut self) -> ControlFlow<B, PoisonPolicy> {
+                    let mut policy = PoisonPolicy::Recover;
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            policy = policy.max(self.$field.poisoned()?);
+                        }
+                    )+
+                    ControlFlow::Continue(policy)
+                }
+            }
+
+            impl<B, $( $type ),+> VisitorMut for ($($type,)+)
+            where
+                $(
+                    $type: VisitorMut<Break = B>
+                ),+
+            {
+                type Break = B;
+
+                fn visit(&mut self, item: &mut dyn Any, event: Event) -> ControlFlow<B, Children> {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        if let Children::Visit = forward_children(member, Signal::from(event), || {
+                            self.$field.visit(item, event)
+                        })? {
+                            children = Children::Visit;
+                        }
+                    )+
+                    ControlFlow::Continue(children)
+                }
+
+                fn visit_entry(
+                    &mut self,
+                    mut entry: MapEntryMut<'_>,
+                    event: Event,
+                ) -> ControlFlow<B, Children> {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        if let Children::Visit = forward_children(member, Signal::from(event), || {
+                            self.$field.visit_entry(entry.reborrow(), event)
+                        })? {
+                            children = Children::Visit;
+                        }
+                    )+
+                    ControlFlow::Continue(children)
+                }
+
+                fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.push_path_segment(segment);
+                        }
+                    )+
+                }
+
+                fn pop_path_segment(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.pop_path_segment();
+                        }
+                    )+
+                }
+
+                fn push_node(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::PushNode) {
+                            self.$field.push_node();
+                        }
+                    )+
+                }
+
+                fn pop_node(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::PopNode) {
+                            self.$field.pop_node();
+                        }
+                    )+
+                }
+
+                fn enter_shared(&mut self, allocation: Allocation) -> Children {
+                    let mut children = Children::Skip;
+                    $(
+                        let member = Member::new(&*self, $field);
+                        let visit = || ControlFlow::<B, _>::Continue(self.$field.enter_shared(allocation));
+                        if let ControlFlow::Continue(Children::Visit) =
+                            forward_children(member, Signal::EnterShared, visit)
+                        {
+                            children = Children::Visit;
+                        }
+                    )+
+                    children
+                }
+
+                fn exit_shared(&mut self) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::ExitShared) {
+                            self.$field.exit_shared();
+                        }
+                    )+
+                }
+
+                fn enter_container(&mut self, container: Container) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.enter_container(container);
+                        }
+                    )+
+                }
+
+                fn exit_container(&mut self, container: Container) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.exit_container(container);
+                        }
+                    )+
+                }
+
+                fn enter_element(&mut self, element: Element) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.enter_element(element);
+                        }
+                    )+
+                }
+
+                fn exit_element(&mut self, element: Element) {
+                    $(
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            self.$field.exit_element(element);
+                        }
+                    )+
+                }
+
+                fn poisoned(&mut self) -> ControlFlow<B, PoisonPolicy> {
+                    let mut policy = PoisonPolicy::Recover;
                     $(
-                        self.$field.drive_mut(visitor);
+                        if forward(Member::new(&*self, $field), Signal::Other) {
+                            policy = policy.max(self.$field.poisoned()?);
+                        }
                     )+
+                    ControlFlow::Continue(policy)
                 }
             }
         )+
@@ -715,15 +2923,21 @@ tuple_impls! {
 macro_rules! trivial_impl {
     ( $type:ty ) => {
         impl Drive for $type {
-            fn drive<V: Visitor>(&self, visitor: &mut V) {
-                visitor.visit(self, Event::Enter);
-                visitor.visit(self, Event::Exit);
+            fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+                self.drive_with_ancestors(visitor, Ancestors::new())
+            }
+
+            fn drive_with_ancestors<V: Visitor>(
+                &self,
+                visitor: &mut V,
+                ancestors: Ancestors<'_>,
+            ) -> ControlFlow<V::Break> {
+                drive_leaf(self, visitor, ancestors)
             }
         }
         impl DriveMut for $type {
-            fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-                visitor.visit(self, Event::Enter);
-                visitor.visit(self, Event::Exit);
+            fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+                drive_leaf_mut(self, visitor)
             }
         }
     };
@@ -758,63 +2972,130 @@ trivial_impl!(String);
 
 #[cfg(feature = "std-types-drive")]
 mod drive_ranges {
-    use super::*;
-    use std::ops::*;
+    use super::{
+        drive_segment, drive_segment_mut, Ancestors, ControlFlow, Drive, DriveMut, PathSegment,
+        Visitor, VisitorMut,
+    };
+    use std::ops::{Range, RangeFrom, RangeInclusive, RangeTo, RangeToInclusive};
 
     impl<T: Drive> Drive for Range<T> {
-        fn drive<V: Visitor>(&self, visitor: &mut V) {
-            self.start.drive(visitor);
-            self.end.drive(visitor);
+        fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+            self.drive_with_ancestors(visitor, Ancestors::new())
+        }
+
+        fn drive_with_ancestors<V: Visitor>(
+            &self,
+            visitor: &mut V,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<V::Break> {
+            drive_segment(visitor, PathSegment::Field("start"), |visitor| {
+                self.start.drive_with_ancestors(visitor, ancestors)
+            })?;
+            drive_segment(visitor, PathSegment::Field("end"), |visitor| {
+                self.end.drive_with_ancestors(visitor, ancestors)
+            })
         }
     }
 
     impl<T: DriveMut> DriveMut for Range<T> {
-        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-            self.start.drive_mut(visitor);
-            self.end.drive_mut(visitor);
+        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+            drive_segment_mut(visitor, PathSegment::Field("start"), |visitor| {
+                self.start.drive_mut(visitor)
+            })?;
+            drive_segment_mut(visitor, PathSegment::Field("end"), |visitor| {
+                self.end.drive_mut(visitor)
+            })
         }
     }
 
     impl<T: Drive> Drive for RangeTo<T> {
-        fn drive<V: Visitor>(&self, visitor: &mut V) {
-            self.end.drive(visitor);
+        fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+            self.drive_with_ancestors(visitor, Ancestors::new())
+        }
+
+        fn drive_with_ancestors<V: Visitor>(
+            &self,
+            visitor: &mut V,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<V::Break> {
+            drive_segment(visitor, PathSegment::Field("end"), |visitor| {
+                self.end.drive_with_ancestors(visitor, ancestors)
+            })
         }
     }
 
     impl<T: DriveMut> DriveMut for RangeTo<T> {
-        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-            self.end.drive_mut(visitor);
+        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+            drive_segment_mut(visitor, PathSegment::Field("end"), |visitor| {
+                self.end.drive_mut(visitor)
+            })
         }
     }
 
     impl<T: Drive> Drive for RangeToInclusive<T> {
-        fn drive<V: Visitor>(&self, visitor: &mut V) {
-            self.end.drive(visitor);
+        fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+            self.drive_with_ancestors(visitor, Ancestors::new())
+        }
+
+        fn drive_with_ancestors<V: Visitor>(
+            &self,
+            visitor: &mut V,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<V::Break> {
+            drive_segment(visitor, PathSegment::Field("end"), |visitor| {
+                self.end.drive_with_ancestors(visitor, ancestors)
+            })
         }
     }
 
     impl<T: DriveMut> DriveMut for RangeToInclusive<T> {
-        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-            self.end.drive_mut(visitor);
+        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+            drive_segment_mut(visitor, PathSegment::Field("end"), |visitor| {
+                self.end.drive_mut(visitor)
+            })
         }
     }
 
     impl<T: Drive> Drive for RangeFrom<T> {
-        fn drive<V: Visitor>(&self, visitor: &mut V) {
-            self.start.drive(visitor);
+        fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+            self.drive_with_ancestors(visitor, Ancestors::new())
+        }
+
+        fn drive_with_ancestors<V: Visitor>(
+            &self,
+            visitor: &mut V,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<V::Break> {
+            drive_segment(visitor, PathSegment::Field("start"), |visitor| {
+                self.start.drive_with_ancestors(visitor, ancestors)
+            })
         }
     }
 
     impl<T: DriveMut> DriveMut for RangeFrom<T> {
-        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
-            self.start.drive_mut(visitor);
+        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+            drive_segment_mut(visitor, PathSegment::Field("start"), |visitor| {
+                self.start.drive_mut(visitor)
+            })
         }
     }
 
     impl<T: Drive> Drive for RangeInclusive<T> {
-        fn drive<V: Visitor>(&self, visitor: &mut V) {
-            self.start().drive(visitor);
-            self.end().drive(visitor);
+        fn drive<V: Visitor>(&self, visitor: &mut V) -> ControlFlow<V::Break> {
+            self.drive_with_ancestors(visitor, Ancestors::new())
+        }
+
+        fn drive_with_ancestors<V: Visitor>(
+            &self,
+            visitor: &mut V,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<V::Break> {
+            drive_segment(visitor, PathSegment::Field("start"), |visitor| {
+                self.start().drive_with_ancestors(visitor, ancestors)
+            })?;
+            drive_segment(visitor, PathSegment::Field("end"), |visitor| {
+                self.end().drive_with_ancestors(visitor, ancestors)
+            })
         }
     }
 
@@ -825,12 +3106,20 @@ mod drive_ranges {
     where
         T: Default,
     {
-        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) {
+        fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
             let placeholder = RangeInclusive::new(T::default(), T::default());
             let bounds = std::mem::replace(self, placeholder);
-            let mut tuple = bounds.into_inner();
-            tuple.drive_mut(visitor);
-            *self = RangeInclusive::new(tuple.0, tuple.1);
+            let (mut start, mut end) = bounds.into_inner();
+            let mut flow = drive_segment_mut(visitor, PathSegment::Field("start"), |visitor| {
+                start.drive_mut(visitor)
+            });
+            if flow.is_continue() {
+                flow = drive_segment_mut(visitor, PathSegment::Field("end"), |visitor| {
+                    end.drive_mut(visitor)
+                });
+            }
+            *self = RangeInclusive::new(start, end);
+            flow
         }
     }
 }
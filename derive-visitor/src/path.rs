@@ -0,0 +1,106 @@
+use std::{any::Any, fmt};
+
+/// A single step from an item to one of its children.
+///
+/// [`Drive`](crate::Drive) implementations report these to the visitor through
+/// [`Visitor::push_path_segment`](crate::Visitor::push_path_segment) and
+/// [`Visitor::pop_path_segment`](crate::Visitor::pop_path_segment)
+/// (or the [`VisitorMut`](crate::VisitorMut) equivalents).
+#[derive(Clone, Copy, Debug)]
+pub enum PathSegment<'a> {
+    /// A named struct / variant field, or a positional field of a tuple or a tuple struct.
+    Field(&'static str),
+    /// An enum variant.
+    Variant(&'static str),
+    /// Position of an element in a sequence or a set.
+    Index(usize),
+    /// Key of a map entry.
+    Key(&'a dyn Any),
+}
+
+/// Location of the item that is currently being visited, relative to the item the traversal started from.
+///
+/// Add it to a visitor and let the visitor forward path segments to it, e.g. with the `path`
+/// parameter of the derived [`Visitor`](crate::Visitor). It renders as `items[3].Directory.items[0]`.
+///
+/// Map keys of primitive and string types are rendered with their [`Debug`](fmt::Debug) representation,
+/// other keys are rendered as `[..]`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Path {
+    rendered: String,
+    lengths: Vec<usize>,
+}
+
+impl Path {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, segment: PathSegment<'_>) {
+        use fmt::Write;
+
+        self.lengths.push(self.rendered.len());
+        // Writing into a `String` never fails
+        match segment {
+            PathSegment::Field(name) | PathSegment::Variant(name) => {
+                if !self.rendered.is_empty() {
+                    self.rendered.push('.');
+                }
+                self.rendered.push_str(name);
+            }
+            PathSegment::Index(index) => {
+                let _ = write!(self.rendered, "[{index}]");
+            }
+            PathSegment::Key(key) => {
+                self.rendered.push('[');
+                let _ = write_key(&mut self.rendered, key);
+                self.rendered.push(']');
+            }
+        }
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(length) = self.lengths.pop() {
+            self.rendered.truncate(length);
+        }
+    }
+
+    /// Number of segments in the path.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lengths.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.rendered
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rendered)
+    }
+}
+
+fn write_key(out: &mut impl fmt::Write, key: &dyn Any) -> fmt::Result {
+    macro_rules! try_debug {
+        ( $( $type:ty ),+ ) => {
+            $(
+                if let Some(key) = key.downcast_ref::<$type>() {
+                    return write!(out, "{key:?}");
+                }
+            )+
+        };
+    }
+
+    try_debug!(String, &'static str, char, bool);
+    try_debug!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+    out.write_str("..")
+}
@@ -0,0 +1,42 @@
+use std::{error::Error, fmt, ops::ControlFlow, sync::LockResult};
+
+/// What a [`Drive`](crate::Drive) implementation does with a poisoned [`Mutex`](std::sync::Mutex) or
+/// [`RwLock`](std::sync::RwLock), see [`Visitor::poisoned`](crate::Visitor::poisoned).
+///
+/// Policies are ordered from the most to the least lenient.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PoisonPolicy {
+    /// Drive the visitor through the value anyway.
+    Recover,
+    /// Don't drive the visitor through the value.
+    Skip,
+    /// Panic, like [`Mutex::lock().unwrap()`](std::sync::Mutex::lock) would.
+    Panic,
+}
+
+/// Error reported by a derived visitor configured with `poisoned = "break"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Poisoned;
+
+impl fmt::Display for Poisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("poisoned lock encountered during traversal")
+    }
+}
+
+impl Error for Poisoned {}
+
+// Unwraps a lock result, asking the visitor what to do if the lock is poisoned
+pub(crate) fn unpoison<G, B>(
+    result: LockResult<G>,
+    poisoned: impl FnOnce() -> ControlFlow<B, PoisonPolicy>,
+) -> ControlFlow<B, Option<G>> {
+    match result {
+        Ok(guard) => ControlFlow::Continue(Some(guard)),
+        Err(error) => match poisoned()? {
+            PoisonPolicy::Recover => ControlFlow::Continue(Some(error.into_inner())),
+            PoisonPolicy::Skip => ControlFlow::Continue(None),
+            PoisonPolicy::Panic => panic!("{}", error),
+        },
+    }
+}
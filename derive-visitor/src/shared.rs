@@ -0,0 +1,82 @@
+use std::collections::HashSet;
+
+/// Identity of the heap allocation behind an `Arc` or an `Rc`.
+///
+/// Reported to [`Visitor::enter_shared`](crate::Visitor::enter_shared) before the visitor is driven through
+/// the allocation's value. Two pointers share their value if and only if they have the same allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Allocation(usize);
+
+impl Allocation {
+    #[must_use]
+    pub fn of<T: ?Sized>(pointer: *const T) -> Self {
+        Self(pointer.cast::<()>() as usize)
+    }
+}
+
+/// Why [`Allocations`] skipped an allocation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Revisit {
+    /// The allocation has already been visited through another pointer.
+    Shared,
+    /// The allocation is being visited, i.e. it is reachable from its own value.
+    Cycle,
+}
+
+/// Set of `Arc` / `Rc` allocations the visitor has been driven through.
+///
+/// Add it to a visitor and let the visitor forward shared allocations to it, e.g. with the `shared`
+/// parameter of the derived [`Visitor`](crate::Visitor), to visit every allocation at most once.
+/// This makes it possible to traverse graphs that share nodes or contain cycles, e.g. through `Weak` pointers
+/// or `Arc<Mutex<T>>`.
+///
+/// Use the `revisit` parameter of the derived visitor to be notified of every skipped allocation.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Allocations {
+    visited: HashSet<Allocation>,
+    open: Vec<Allocation>,
+}
+
+impl Allocations {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that the visitor has reached `allocation`, and tells whether to drive it through its value.
+    ///
+    /// Returns `None` if the allocation hasn't been visited yet, or the reason to skip it.
+    pub fn enter(&mut self, allocation: Allocation) -> Option<Revisit> {
+        let revisit = if self.visited.insert(allocation) {
+            None
+        } else if self.open.contains(&allocation) {
+            Some(Revisit::Cycle)
+        } else {
+            Some(Revisit::Shared)
+        };
+        self.open.push(allocation);
+        revisit
+    }
+
+    /// Records that the visitor is done with the allocation passed to the matching [`enter`](Allocations::enter).
+    pub fn exit(&mut self) {
+        self.open.pop();
+    }
+
+    /// Whether the visitor has been driven through `allocation`.
+    #[must_use]
+    pub fn contains(&self, allocation: Allocation) -> bool {
+        self.visited.contains(&allocation)
+    }
+
+    /// Number of allocations visited so far.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.visited.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.visited.is_empty()
+    }
+}
@@ -0,0 +1,150 @@
+use std::{any::type_name, cell::RefCell, ops::ControlFlow};
+
+use crate::{Children, Event};
+
+// A tuple of visitors can't hold any state besides its elements, so the subtrees its elements have
+// asked to skip are kept per thread, keyed by the tuple and the element's index.
+//
+// Every skipped subtree is closed by a notification the `Drive` implementations deliver even if
+// the traversal is interrupted, i.e. the `pop_node` around a skipped item or the `exit_shared` of
+// a skipped allocation, so that nothing is left over once the traversal returns.
+
+thread_local! {
+    static SKIPPED: RefCell<Vec<Skipped>> = const { RefCell::new(Vec::new()) };
+}
+
+/// An element of a tuple of visitors.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Member {
+    tuple: usize,
+    tuple_type: &'static str,
+    index: usize,
+}
+
+impl Member {
+    pub(crate) fn new<T>(tuple: &T, index: usize) -> Self {
+        Self {
+            tuple: std::ptr::from_ref(tuple).cast::<()>() as usize,
+            tuple_type: type_name::<T>(),
+            index,
+        }
+    }
+}
+
+/// Notification a tuple of visitors is about to forward to one of its elements.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Signal {
+    Enter,
+    Exit,
+    PushNode,
+    PopNode,
+    EnterShared,
+    ExitShared,
+    Other,
+}
+
+impl From<Event> for Signal {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::Enter => Self::Enter,
+            Event::Exit => Self::Exit,
+        }
+    }
+}
+
+/// What an element asked to skip.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Skip {
+    /// The children of the item or map entry it has just entered.
+    Children,
+    /// The value of the allocation it has just entered.
+    Shared,
+}
+
+struct Skipped {
+    member: Member,
+    skip: Skip,
+    items: usize,
+    nodes: usize,
+    shared: usize,
+}
+
+/// Records that `member` has asked to skip a subtree.
+fn skip(member: Member, skip: Skip) {
+    SKIPPED.with(|skipped| {
+        skipped.borrow_mut().push(Skipped {
+            member,
+            skip,
+            items: 0,
+            nodes: 0,
+            shared: 0,
+        });
+    });
+}
+
+/// Tells whether to forward `signal` to `member`, i.e. whether `member` is outside of the subtrees
+/// it has asked to skip, or `signal` closes such a subtree.
+pub(crate) fn forward(member: Member, signal: Signal) -> bool {
+    SKIPPED.with(|skipped| {
+        let mut skipped = skipped.borrow_mut();
+        let Some(position) = skipped.iter().rposition(|skipped| skipped.member == member) else {
+            return true;
+        };
+        let current = &mut skipped[position];
+        let closed = match signal {
+            Signal::Enter => {
+                current.items += 1;
+                false
+            }
+            Signal::Exit if current.items == 0 => current.skip == Skip::Children,
+            Signal::Exit => {
+                current.items -= 1;
+                false
+            }
+            Signal::PushNode => {
+                current.nodes += 1;
+                false
+            }
+            // The item's `Exit` isn't delivered if the traversal has been interrupted
+            Signal::PopNode if current.nodes == 0 => current.skip == Skip::Children,
+            Signal::PopNode => {
+                current.nodes -= 1;
+                false
+            }
+            Signal::EnterShared => {
+                current.shared += 1;
+                false
+            }
+            Signal::ExitShared if current.shared == 0 => current.skip == Skip::Shared,
+            Signal::ExitShared => {
+                current.shared -= 1;
+                false
+            }
+            Signal::Other => false,
+        };
+        if closed {
+            skipped.remove(position);
+        }
+        closed
+    })
+}
+
+/// Forwards a notification that may skip a subtree to `member` using `visit`, see [`forward`].
+///
+/// Returns [`Children::Skip`] if the notification isn't forwarded.
+pub(crate) fn forward_children<B>(
+    member: Member,
+    signal: Signal,
+    visit: impl FnOnce() -> ControlFlow<B, Children>,
+) -> ControlFlow<B, Children> {
+    if !forward(member, signal) {
+        return ControlFlow::Continue(Children::Skip);
+    }
+    let children = visit()?;
+    match (children, signal) {
+        (Children::Skip, Signal::Enter) => skip(member, Skip::Children),
+        (Children::Skip, Signal::EnterShared) => skip(member, Skip::Shared),
+        _ => {}
+    }
+    ControlFlow::Continue(children)
+}
@@ -0,0 +1,87 @@
+use std::ops::ControlFlow;
+
+use crate::{
+    container::{drive_container, drive_container_mut},
+    Ancestors, ContainerKind, DerefAndDrive, DerefAndDriveMut, Drive, DriveMut, Visitor,
+    VisitorMut,
+};
+
+/// Return value of a method referenced by `#[drive(children = "path")]`, see [`Drive`].
+///
+/// Implemented for every type that implements [`Drive`], and for anything that can be iterated over
+/// references to such types, e.g. `&Vec<T>`, `Option<&T>` or an iterator of `&T`. Iterators are reported
+/// as [`ContainerKind::Sequence`] containers, and every item is reported under its position in the iteration.
+/// The type parameter only tells these implementations apart and is always inferred.
+pub trait VirtualChildren<M> {
+    fn drive_virtual<V: Visitor>(
+        self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break>;
+}
+
+/// Return value of a method referenced by `#[drive(children_mut = "path")]`, see [`VirtualChildren`].
+pub trait VirtualChildrenMut<M> {
+    fn drive_virtual_mut<V: VisitorMut>(self, visitor: &mut V) -> ControlFlow<V::Break>;
+}
+
+// Markers of the implementations for values and for iterators. They are public so that they can be used
+// as type arguments of the public traits, but can't be named outside of this crate
+#[doc(hidden)]
+pub enum ValueChildren {}
+
+#[doc(hidden)]
+pub enum IterChildren {}
+
+impl<T: Drive> VirtualChildren<ValueChildren> for T {
+    fn drive_virtual<V: Visitor>(
+        self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        self.drive_with_ancestors(visitor, ancestors)
+    }
+}
+
+// Items are collected first, since the length of a container has to be known when entering it
+impl<I> VirtualChildren<IterChildren> for I
+where
+    I: IntoIterator,
+    I::Item: DerefAndDrive,
+{
+    fn drive_virtual<V: Visitor>(
+        self,
+        visitor: &mut V,
+        ancestors: Ancestors<'_>,
+    ) -> ControlFlow<V::Break> {
+        let items: Vec<I::Item> = self.into_iter().collect();
+        drive_container(
+            visitor,
+            ContainerKind::Sequence,
+            items.into_iter(),
+            |visitor, index, item| item.deref_and_drive(index, visitor, ancestors),
+        )
+    }
+}
+
+impl<T: DriveMut> VirtualChildrenMut<ValueChildren> for T {
+    fn drive_virtual_mut<V: VisitorMut>(mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        self.drive_mut(visitor)
+    }
+}
+
+impl<I> VirtualChildrenMut<IterChildren> for I
+where
+    I: IntoIterator,
+    I::Item: DerefAndDriveMut,
+{
+    fn drive_virtual_mut<V: VisitorMut>(self, visitor: &mut V) -> ControlFlow<V::Break> {
+        let items: Vec<I::Item> = self.into_iter().collect();
+        drive_container_mut(
+            visitor,
+            ContainerKind::Sequence,
+            items.into_iter(),
+            |visitor, index, item| item.deref_and_drive_mut(index, visitor),
+        )
+    }
+}
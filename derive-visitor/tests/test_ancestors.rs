@@ -0,0 +1,108 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{Ancestors, Drive, Visitor};
+
+#[derive(Drive)]
+struct Function {
+    #[drive(skip)]
+    name: &'static str,
+    body: Vec<Statement>,
+}
+
+#[derive(Drive)]
+#[drive(skip)]
+struct Statement {
+    expr: Expr,
+}
+
+#[derive(Drive)]
+enum Expr {
+    Call(#[drive(skip)] &'static str),
+    Closure(Box<Function>),
+}
+
+#[derive(Default, Visitor)]
+#[visitor(Expr(enter), ancestors)]
+struct Calls {
+    found: Vec<(&'static str, &'static str, usize)>,
+}
+
+impl Calls {
+    fn enter_expr(&mut self, expr: &Expr, ancestors: Ancestors<'_>) {
+        if let Expr::Call(callee) = expr {
+            let caller = ancestors.nearest::<Function>().map_or("", |f| f.name);
+            self.found.push((caller, callee, ancestors.len()));
+        }
+    }
+}
+
+fn example() -> Function {
+    Function {
+        name: "main",
+        body: vec![
+            Statement {
+                expr: Expr::Closure(Box::new(Function {
+                    name: "callback",
+                    body: vec![Statement {
+                        expr: Expr::Call("print"),
+                    }],
+                })),
+            },
+            Statement {
+                expr: Expr::Call("run"),
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_ancestors() {
+    let mut visitor = Calls::default();
+    let _ = example().drive(&mut visitor);
+    // Statements are skipped at the type level, so they are never ancestors
+    assert_eq!(
+        visitor.found,
+        [("callback", "print", 3), ("main", "run", 1)]
+    );
+}
+
+#[test]
+fn test_ancestors_parent() {
+    #[derive(Default, Visitor)]
+    #[visitor(Function(enter), ancestors)]
+    struct FirstNested;
+
+    impl FirstNested {
+        fn enter_function(
+            &mut self,
+            _function: &Function,
+            ancestors: Ancestors<'_>,
+        ) -> ControlFlow<()> {
+            match ancestors.parent() {
+                Some(parent) if parent.is::<Expr>() => ControlFlow::Break(()),
+                _ => ControlFlow::Continue(()),
+            }
+        }
+    }
+
+    assert_eq!(example().drive(&mut FirstNested), ControlFlow::Break(()));
+}
+
+#[test]
+fn test_ancestors_root() {
+    #[derive(Default, Visitor)]
+    #[visitor(Function(enter), ancestors)]
+    struct Roots(usize);
+
+    impl Roots {
+        fn enter_function(&mut self, _function: &Function, ancestors: Ancestors<'_>) {
+            if ancestors.is_empty() {
+                self.0 += 1;
+            }
+        }
+    }
+
+    let mut visitor = Roots::default();
+    let _ = example().drive(&mut visitor);
+    assert_eq!(visitor.0, 1);
+}
@@ -0,0 +1,229 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::ControlFlow,
+};
+
+use derive_visitor::{
+    Children, Container, ContainerKind, Drive, DriveMut, Element, Event, Visitor, VisitorMut,
+};
+
+#[derive(Drive, DriveMut)]
+struct Module {
+    items: Vec<Item>,
+    doc: Option<Item>,
+    #[drive(entries)]
+    by_name: BTreeMap<Name, Item>,
+}
+
+#[derive(Drive, DriveMut, PartialEq, Eq, PartialOrd, Ord)]
+struct Name(#[drive(skip)] String);
+
+#[derive(Drive, DriveMut)]
+struct Item(#[drive(skip)] u32);
+
+// Records the container and the position of every item
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(
+    Item(enter),
+    container(enter = "open_container", exit = "close_container"),
+    element(enter = "open_element", exit = "close_element")
+)]
+struct Positions {
+    containers: Vec<Container>,
+    element: Option<Element>,
+    items: Vec<(u32, Container, Element)>,
+    empty: Vec<ContainerKind>,
+}
+
+impl Positions {
+    fn enter_item(&mut self, item: &Item) {
+        let container = *self.containers.last().unwrap();
+        self.items.push((item.0, container, self.element.unwrap()));
+    }
+    fn open_container(&mut self, container: Container) {
+        self.containers.push(container);
+    }
+    fn close_container(&mut self, container: Container) {
+        assert_eq!(self.containers.pop(), Some(container));
+        if container.len == 0 {
+            self.empty.push(container.kind);
+        }
+    }
+    fn open_element(&mut self, element: Element) {
+        assert_eq!(self.element.replace(element), None);
+    }
+    fn close_element(&mut self, element: Element) {
+        assert_eq!(self.element.take(), Some(element));
+    }
+}
+
+#[test]
+fn test_container_events() {
+    let mut module = Module {
+        items: vec![Item(1), Item(2)],
+        doc: None,
+        by_name: vec![(Name("main".into()), Item(3))].into_iter().collect(),
+    };
+    let sequence = Container {
+        kind: ContainerKind::Sequence,
+        len: 2,
+    };
+    let map = Container {
+        kind: ContainerKind::Map,
+        len: 1,
+    };
+    let expected = [
+        (
+            1,
+            sequence,
+            Element {
+                index: 0,
+                first: true,
+                last: false,
+            },
+        ),
+        (
+            2,
+            sequence,
+            Element {
+                index: 1,
+                first: false,
+                last: true,
+            },
+        ),
+        (
+            3,
+            map,
+            Element {
+                index: 0,
+                first: true,
+                last: true,
+            },
+        ),
+    ];
+
+    let mut positions = Positions::default();
+    let _ = module.drive(&mut positions);
+    assert_eq!(positions.items, expected);
+    assert_eq!(positions.empty, [ContainerKind::Option]);
+    assert!(positions.containers.is_empty());
+
+    let mut positions = Positions::default();
+    let _ = module.drive_mut(&mut positions);
+    assert_eq!(positions.items, expected);
+    assert_eq!(positions.empty, [ContainerKind::Option]);
+}
+
+#[test]
+fn test_container_events_after_break() {
+    #[derive(Default, Visitor)]
+    #[visitor(
+        Item(enter),
+        container(enter = "open_container", exit = "close_container"),
+        element(enter = "open_element", exit = "close_element"),
+        break = "u32"
+    )]
+    struct FirstItem {
+        containers: usize,
+        open: usize,
+    }
+
+    impl FirstItem {
+        fn enter_item(&mut self, item: &Item) -> ControlFlow<u32> {
+            ControlFlow::Break(item.0)
+        }
+        fn open_container(&mut self, _container: Container) {
+            self.containers += 1;
+            self.open += 1;
+        }
+        fn close_container(&mut self, _container: Container) {
+            self.open -= 1;
+        }
+        fn open_element(&mut self, _element: Element) {
+            self.open += 1;
+        }
+        fn close_element(&mut self, _element: Element) {
+            self.open -= 1;
+        }
+    }
+
+    let module = Module {
+        items: vec![Item(1), Item(2)],
+        doc: Some(Item(3)),
+        by_name: BTreeMap::new(),
+    };
+    let mut visitor = FirstItem::default();
+    assert_eq!(module.drive(&mut visitor), ControlFlow::Break(1));
+    // The open element and container are still exited, but no other container is entered
+    assert_eq!((visitor.containers, visitor.open), (1, 0));
+}
+
+#[test]
+fn test_container_kind() {
+    struct Kinds(Vec<ContainerKind>);
+
+    impl Visitor for Kinds {
+        type Break = ();
+
+        fn visit(&mut self, _item: &dyn Any, _event: Event) -> ControlFlow<(), Children> {
+            ControlFlow::Continue(Children::Visit)
+        }
+
+        fn enter_container(&mut self, container: Container) {
+            self.0.push(container.kind);
+        }
+    }
+
+    let containers = (
+        BTreeSet::<Name>::new(),
+        [Item(0)],
+        HashMap::<Name, Item>::new(),
+        Ok::<Item, String>(Item(1)),
+    );
+    let mut kinds = Kinds(Vec::new());
+    let _ = containers.drive(&mut kinds);
+    assert_eq!(
+        kinds.0,
+        [
+            ContainerKind::Set,
+            ContainerKind::Sequence,
+            ContainerKind::Map,
+            ContainerKind::Result
+        ]
+    );
+}
+
+#[test]
+fn test_container_events_result() {
+    #[derive(Drive, DriveMut)]
+    struct Outcomes {
+        success: Result<Item, String>,
+        failure: Result<Item, String>,
+    }
+
+    let mut outcomes = Outcomes {
+        success: Ok(Item(1)),
+        failure: Err("failed".into()),
+    };
+    let ok = Container {
+        kind: ContainerKind::Result,
+        len: 1,
+    };
+    let only = Element {
+        index: 0,
+        first: true,
+        last: true,
+    };
+
+    // Errors aren't driven through, so the failure is an empty container
+    let mut positions = Positions::default();
+    let _ = outcomes.drive(&mut positions);
+    assert_eq!(positions.items, [(1, ok, only)]);
+    assert_eq!(positions.empty, [ContainerKind::Result]);
+
+    let mut positions = Positions::default();
+    let _ = outcomes.drive_mut(&mut positions);
+    assert_eq!(positions.items, [(1, ok, only)]);
+    assert_eq!(positions.empty, [ContainerKind::Result]);
+}
@@ -0,0 +1,98 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{Context, Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Block {
+    statements: Vec<Statement>,
+}
+
+#[derive(Drive, DriveMut)]
+#[drive(skip)]
+struct Statement {
+    expr: Expr,
+}
+
+#[derive(Drive, DriveMut)]
+enum Expr {
+    Literal,
+    Block(Box<Block>),
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Block(enter), Expr(enter), context = "context")]
+struct Positions {
+    context: Context,
+    positions: Vec<(usize, usize)>,
+}
+
+impl Positions {
+    fn enter_block(&mut self, _block: &Block) {
+        self.positions
+            .push((self.context.depth(), self.context.index()));
+    }
+    fn enter_expr(&mut self, _expr: &Expr) {
+        self.positions
+            .push((self.context.depth(), self.context.index()));
+    }
+}
+
+fn example() -> Block {
+    Block {
+        statements: vec![
+            Statement {
+                expr: Expr::Block(Box::new(Block {
+                    statements: vec![Statement {
+                        expr: Expr::Literal,
+                    }],
+                })),
+            },
+            Statement {
+                expr: Expr::Literal,
+            },
+        ],
+    }
+}
+
+// Statements are skipped at the type level, but still count as nodes
+const EXPECTED: [(usize, usize); 5] = [(0, 0), (2, 2), (3, 3), (5, 5), (2, 7)];
+
+#[test]
+fn test_context() {
+    let mut visitor = Positions::default();
+    let _ = example().drive(&mut visitor);
+    assert_eq!(visitor.positions, EXPECTED);
+    assert_eq!(visitor.context.nodes_driven(), 8);
+    assert_eq!(visitor.context.depth(), 0);
+}
+
+#[test]
+fn test_context_mut() {
+    let mut visitor = Positions::default();
+    let _ = example().drive_mut(&mut visitor);
+    assert_eq!(visitor.positions, EXPECTED);
+    assert_eq!(visitor.context.nodes_driven(), 8);
+}
+
+#[test]
+fn test_context_after_break() {
+    #[derive(Default, Visitor)]
+    #[visitor(Expr(enter), context = "context", break = "usize")]
+    struct FirstLiteral {
+        context: Context,
+    }
+
+    impl FirstLiteral {
+        fn enter_expr(&mut self, expr: &Expr) -> ControlFlow<usize> {
+            match expr {
+                Expr::Literal => ControlFlow::Break(self.context.depth()),
+                Expr::Block(_) => ControlFlow::Continue(()),
+            }
+        }
+    }
+
+    let mut visitor = FirstLiteral::default();
+    assert_eq!(example().drive(&mut visitor), ControlFlow::Break(5));
+    assert_eq!(visitor.context.depth(), 0);
+    assert_eq!(visitor.context.nodes_driven(), 6);
+}
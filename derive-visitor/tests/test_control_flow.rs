@@ -0,0 +1,196 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{Children, Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Tree {
+    #[drive(skip)]
+    name: String,
+    children: Vec<Tree>,
+}
+
+impl Tree {
+    fn new(name: &str, children: Vec<Tree>) -> Self {
+        Self {
+            name: name.to_string(),
+            children,
+        }
+    }
+}
+
+#[derive(Default, Visitor)]
+#[visitor(Tree, break = "String")]
+struct FindNode {
+    target: &'static str,
+    entered: Vec<String>,
+    exited: Vec<String>,
+}
+
+impl FindNode {
+    fn enter_tree(&mut self, tree: &Tree) -> ControlFlow<String> {
+        self.entered.push(tree.name.clone());
+        if tree.name == self.target {
+            ControlFlow::Break(tree.name.to_uppercase())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+    fn exit_tree(&mut self, tree: &Tree) {
+        self.exited.push(tree.name.clone());
+    }
+}
+
+fn example_tree() -> Tree {
+    Tree::new(
+        "root",
+        vec![
+            Tree::new("a", vec![Tree::new("a1", vec![]), Tree::new("a2", vec![])]),
+            Tree::new("b", vec![Tree::new("b1", vec![])]),
+        ],
+    )
+}
+
+#[test]
+fn test_break_stops_traversal() {
+    let mut visitor = FindNode {
+        target: "a2",
+        ..FindNode::default()
+    };
+    let flow = example_tree().drive(&mut visitor);
+    assert_eq!(flow, ControlFlow::Break("A2".to_string()));
+    assert_eq!(visitor.entered, ["root", "a", "a1", "a2"]);
+    assert_eq!(visitor.exited, ["a1"]);
+}
+
+#[test]
+fn test_continue_visits_everything() {
+    let mut visitor = FindNode {
+        target: "missing",
+        ..FindNode::default()
+    };
+    let flow = example_tree().drive(&mut visitor);
+    assert_eq!(flow, ControlFlow::Continue(()));
+    assert_eq!(visitor.entered.len(), 6);
+    assert_eq!(visitor.exited.len(), 6);
+}
+
+#[test]
+fn test_break_mut() {
+    #[derive(VisitorMut)]
+    #[visitor(Tree(enter), break = "usize")]
+    struct RenameUntil {
+        renamed: usize,
+        limit: usize,
+    }
+
+    impl RenameUntil {
+        fn enter_tree(&mut self, tree: &mut Tree) -> ControlFlow<usize> {
+            if self.renamed == self.limit {
+                return ControlFlow::Break(self.renamed);
+            }
+            tree.name = tree.name.to_uppercase();
+            self.renamed += 1;
+            ControlFlow::Continue(())
+        }
+    }
+
+    let mut tree = example_tree();
+    let flow = tree.drive_mut(&mut RenameUntil {
+        renamed: 0,
+        limit: 3,
+    });
+    assert_eq!(flow, ControlFlow::Break(3));
+    assert_eq!(tree.name, "ROOT");
+    assert_eq!(tree.children[0].name, "A");
+    assert_eq!(tree.children[0].children[0].name, "A1");
+    assert_eq!(tree.children[0].children[1].name, "a2");
+    assert_eq!(tree.children[1].name, "b");
+}
+
+#[derive(Drive, DriveMut)]
+enum Item {
+    Module(Tree),
+    Generated(Tree),
+}
+
+#[derive(Default, Visitor)]
+#[visitor(Tree, Item(enter))]
+struct SkipGenerated {
+    entered: Vec<String>,
+    exited: Vec<String>,
+}
+
+impl SkipGenerated {
+    fn enter_item(&mut self, item: &Item) -> Children {
+        match item {
+            Item::Module(_) => Children::Visit,
+            Item::Generated(_) => Children::Skip,
+        }
+    }
+    fn enter_tree(&mut self, tree: &Tree) -> Children {
+        self.entered.push(tree.name.clone());
+        if tree.name.starts_with("gen") {
+            Children::Skip
+        } else {
+            Children::Visit
+        }
+    }
+    fn exit_tree(&mut self, tree: &Tree) {
+        self.exited.push(tree.name.clone());
+    }
+}
+
+#[test]
+fn test_skip_children() {
+    let tree = Tree::new(
+        "root",
+        vec![
+            Tree::new("generated", vec![Tree::new("hidden", vec![])]),
+            Tree::new("a", vec![Tree::new("a1", vec![])]),
+        ],
+    );
+    let mut visitor = SkipGenerated::default();
+    assert_eq!(tree.drive(&mut visitor), ControlFlow::Continue(()));
+    assert_eq!(visitor.entered, ["root", "generated", "a", "a1"]);
+    assert_eq!(visitor.exited, ["generated", "a1", "a", "root"]);
+
+    let items = vec![
+        Item::Generated(Tree::new("hidden", vec![])),
+        Item::Module(Tree::new("b", vec![])),
+    ];
+    let mut visitor = SkipGenerated::default();
+    let _ = items.drive(&mut visitor);
+    assert_eq!(visitor.entered, ["b"]);
+    assert_eq!(visitor.exited, ["b"]);
+}
+
+#[test]
+fn test_skip_children_mut() {
+    #[derive(VisitorMut)]
+    #[visitor(Tree)]
+    struct ShoutUntilGenerated;
+
+    impl ShoutUntilGenerated {
+        fn enter_tree(&mut self, tree: &mut Tree) -> ControlFlow<(), Children> {
+            let children = if tree.name.starts_with("gen") {
+                Children::Skip
+            } else {
+                Children::Visit
+            };
+            tree.name = tree.name.to_uppercase();
+            ControlFlow::Continue(children)
+        }
+        fn exit_tree(&mut self, tree: &mut Tree) {
+            tree.name.push('!');
+        }
+    }
+
+    let mut tree = Tree::new(
+        "root",
+        vec![Tree::new("generated", vec![Tree::new("hidden", vec![])])],
+    );
+    let _ = tree.drive_mut(&mut ShoutUntilGenerated);
+    assert_eq!(tree.name, "ROOT!");
+    assert_eq!(tree.children[0].name, "GENERATED!");
+    assert_eq!(tree.children[0].children[0].name, "hidden");
+}
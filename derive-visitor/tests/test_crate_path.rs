@@ -0,0 +1,69 @@
+mod framework {
+    pub mod visitor {
+        pub use derive_visitor::*;
+    }
+}
+
+use framework::visitor::{visitor_enter_fn, Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+#[drive(crate = "framework::visitor")]
+struct Directory {
+    items: Vec<Item>,
+}
+
+#[derive(Drive, DriveMut)]
+#[drive(crate = "crate::framework::visitor")]
+enum Item {
+    File(File),
+    Directory(Directory),
+}
+
+#[derive(Drive, DriveMut)]
+#[drive(crate = "framework::visitor")]
+struct File {
+    #[drive(skip)]
+    size: u64,
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(crate = "framework::visitor", File(enter))]
+struct SizeCounter {
+    size: u64,
+}
+
+impl SizeCounter {
+    fn enter_file(&mut self, file: &File) {
+        self.size += file.size;
+    }
+}
+
+#[test]
+fn test_crate_path() {
+    let mut directory = Directory {
+        items: vec![
+            Item::File(File { size: 1 }),
+            Item::Directory(Directory {
+                items: vec![Item::File(File { size: 2 })],
+            }),
+        ],
+    };
+
+    let mut counter = SizeCounter::default();
+    let _ = directory.drive(&mut counter);
+    assert_eq!(counter.size, 3);
+
+    let mut counter = SizeCounter::default();
+    let _ = directory.drive_mut(&mut counter);
+    assert_eq!(counter.size, 3);
+}
+
+#[test]
+fn test_crate_path_functions() {
+    let directory = Directory {
+        items: vec![Item::File(File { size: 1 }), Item::File(File { size: 2 })],
+    };
+    let mut files = 0;
+    let _ = directory.drive(&mut visitor_enter_fn(|_: &File| files += 1));
+    assert_eq!(files, 2);
+}
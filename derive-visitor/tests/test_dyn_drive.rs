@@ -0,0 +1,93 @@
+use std::{ops::ControlFlow, sync::Arc};
+
+use derive_visitor::{
+    visitor_enter_fn, visitor_enter_fn_mut, Drive, DriveMut, DynDrive, DynDriveMut, Path, Visitor,
+};
+
+#[derive(Drive)]
+struct Document {
+    blocks: Vec<Box<dyn DynDrive>>,
+}
+
+#[derive(Drive, DriveMut)]
+struct Paragraph {
+    #[drive(skip)]
+    text: String,
+}
+
+#[derive(Drive, DriveMut)]
+struct Quote {
+    paragraphs: Vec<Paragraph>,
+}
+
+fn paragraph(text: &str) -> Paragraph {
+    Paragraph {
+        text: text.to_string(),
+    }
+}
+
+fn example() -> Document {
+    Document {
+        blocks: vec![
+            Box::new(paragraph("intro")),
+            Box::new(Quote {
+                paragraphs: vec![paragraph("quoted"), paragraph("TODO")],
+            }),
+            Box::new(paragraph("outro")),
+        ],
+    }
+}
+
+#[test]
+fn test_dyn_drive() {
+    let mut texts = Vec::new();
+    let _ = example().drive(&mut visitor_enter_fn(|p: &Paragraph| {
+        texts.push(p.text.clone());
+    }));
+    assert_eq!(texts, ["intro", "quoted", "TODO", "outro"]);
+}
+
+#[test]
+fn test_dyn_drive_break() {
+    #[derive(Default, Visitor)]
+    #[visitor(Paragraph(enter), path = "path", break = "String")]
+    struct FindTodo {
+        path: Path,
+    }
+
+    impl FindTodo {
+        fn enter_paragraph(&mut self, paragraph: &Paragraph) -> ControlFlow<String> {
+            if paragraph.text == "TODO" {
+                ControlFlow::Break(self.path.to_string())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    let flow = example().drive(&mut FindTodo::default());
+    assert_eq!(
+        flow,
+        ControlFlow::Break("blocks[1].paragraphs[1]".to_string())
+    );
+}
+
+#[test]
+fn test_dyn_drive_mut() {
+    let mut blocks: Vec<Box<dyn DynDriveMut + Send + Sync>> = vec![
+        Box::new(paragraph("a")),
+        Box::new(Quote {
+            paragraphs: vec![paragraph("b")],
+        }),
+    ];
+    let _ = blocks.drive_mut(&mut visitor_enter_fn_mut(|p: &mut Paragraph| {
+        p.text.make_ascii_uppercase();
+    }));
+
+    let shared: Arc<Vec<Box<dyn DynDriveMut + Send + Sync>>> = Arc::new(blocks);
+    let mut texts = String::new();
+    let _ = shared.drive(&mut visitor_enter_fn(|p: &Paragraph| {
+        texts.push_str(&p.text)
+    }));
+    assert_eq!(texts, "AB");
+}
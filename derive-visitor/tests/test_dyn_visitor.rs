@@ -0,0 +1,74 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{
+    visitor_enter_fn, visitor_enter_fn_mut, Drive, DriveMut, Path, Visitor, VisitorMut,
+};
+
+#[derive(Drive, DriveMut)]
+struct Folder {
+    files: Vec<File>,
+}
+
+#[derive(Drive, DriveMut)]
+struct File {
+    #[drive(skip)]
+    size: u32,
+}
+
+fn example() -> Folder {
+    Folder {
+        files: vec![File { size: 1 }, File { size: 10 }],
+    }
+}
+
+#[derive(Default, Visitor)]
+#[visitor(File(enter), path = "path", break = "String")]
+struct FindLarge {
+    path: Path,
+}
+
+impl FindLarge {
+    fn enter_file(&mut self, file: &File) -> ControlFlow<String> {
+        if file.size > 5 {
+            ControlFlow::Break(self.path.to_string())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+#[test]
+fn test_drive_dyn() {
+    let mut visitor: Box<dyn Visitor<Break = String>> = Box::new(FindLarge::default());
+    let flow = example().drive_dyn(visitor.as_mut());
+    assert_eq!(flow, ControlFlow::Break("files[1]".to_string()));
+}
+
+#[test]
+fn test_drive_boxed_and_borrowed() {
+    let mut total = 0;
+    let mut visitor = visitor_enter_fn(|file: &File| total += file.size);
+    let _ = example().drive(&mut &mut visitor);
+    let _ = example().drive(&mut Box::new(&mut visitor));
+    drop(visitor);
+    assert_eq!(total, 22);
+
+    // Hooks are forwarded as well
+    let mut boxed: Box<dyn Visitor<Break = String>> = Box::new(FindLarge::default());
+    let flow = example().drive(&mut boxed);
+    assert_eq!(flow, ControlFlow::Break("files[1]".to_string()));
+}
+
+#[test]
+fn test_drive_dyn_mut() {
+    let mut visitors: Vec<Box<dyn VisitorMut<Break = ()>>> = vec![
+        Box::new(visitor_enter_fn_mut(|file: &mut File| file.size *= 2)),
+        Box::new(visitor_enter_fn_mut(|file: &mut File| file.size += 1)),
+    ];
+    let mut folder = example();
+    for visitor in &mut visitors {
+        let _ = folder.drive_dyn_mut(visitor.as_mut());
+    }
+    let sizes: Vec<_> = folder.files.iter().map(|file| file.size).collect();
+    assert_eq!(sizes, [3, 21]);
+}
@@ -0,0 +1,208 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    ops::ControlFlow,
+};
+
+use derive_visitor::{
+    Ancestors, Children, Drive, DriveMut, Event, MapEntry, MapEntryMut, Visitor, VisitorMut,
+};
+
+#[derive(Drive, DriveMut, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+struct Port(#[drive(skip)] u16);
+
+#[derive(Drive, DriveMut, Debug, PartialEq)]
+struct Service(#[drive(skip)] String);
+
+#[derive(Drive, DriveMut)]
+struct Host {
+    #[drive(entries)]
+    services: BTreeMap<Port, Service>,
+}
+
+#[test]
+fn test_entry_events() {
+    // Checks that keys and values are visited inside of their entry
+    #[derive(Default, Visitor)]
+    #[visitor(entries, Port(enter), Service(enter))]
+    struct Listing {
+        open: Option<u16>,
+        ports: Vec<u16>,
+        services: usize,
+    }
+
+    impl Listing {
+        fn enter_entry(&mut self, entry: MapEntry<'_, Port, Service>) {
+            assert_eq!(self.open.replace(entry.key().0), None);
+        }
+        fn exit_entry(&mut self, entry: MapEntry<'_, Port, Service>) {
+            assert_eq!(self.open.take(), Some(entry.key().0));
+            self.ports.push(entry.key().0);
+        }
+        fn enter_port(&mut self, port: &Port) {
+            assert_eq!(self.open, Some(port.0));
+        }
+        fn enter_service(&mut self, _service: &Service) {
+            assert!(self.open.is_some());
+            self.services += 1;
+        }
+    }
+
+    let host = Host {
+        services: vec![
+            (Port(22), Service("ssh".into())),
+            (Port(80), Service("http".into())),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    let mut listing = Listing::default();
+    let _ = host.drive(&mut listing);
+    assert_eq!(listing.ports, [22, 80]);
+    assert_eq!(listing.services, 2);
+}
+
+#[test]
+fn test_entry_mut() {
+    #[derive(VisitorMut)]
+    #[visitor(entries(enter = "rename"), Port(enter))]
+    struct Rename;
+
+    impl Rename {
+        fn rename(&mut self, mut entry: MapEntryMut<'_, Port, Service>) {
+            let name = format!("{}:{}", entry.value().0, entry.key().0);
+            entry.value_mut().0 = name;
+        }
+        fn enter_port(&mut self, _port: &mut Port) {
+            panic!("keys aren't visited by mutable traversals");
+        }
+    }
+
+    let mut host = Host {
+        services: vec![(Port(22), Service("ssh".into()))]
+            .into_iter()
+            .collect(),
+    };
+    let _ = host.drive_mut(&mut Rename);
+    assert_eq!(host.services[&Port(22)], Service("ssh:22".into()));
+}
+
+#[test]
+fn test_entry_ancestors() {
+    #[derive(Default, Visitor)]
+    #[visitor(entries(enter), Service(enter), ancestors)]
+    struct Owners {
+        services: usize,
+    }
+
+    impl Owners {
+        fn enter_entry(&mut self, _entry: MapEntry<'_, Port, Service>, ancestors: Ancestors<'_>) {
+            assert!(ancestors.parent().unwrap().is::<Host>());
+        }
+        fn enter_service(&mut self, _service: &Service, ancestors: Ancestors<'_>) {
+            // Entries borrow the map, so they are never ancestors
+            assert!(ancestors.parent().unwrap().is::<Host>());
+            assert_eq!(ancestors.len(), 1);
+            self.services += 1;
+        }
+    }
+
+    let host = Host {
+        services: vec![(Port(22), Service("ssh".into()))]
+            .into_iter()
+            .collect(),
+    };
+    let mut owners = Owners::default();
+    let _ = host.drive(&mut owners);
+    assert_eq!(owners.services, 1);
+}
+
+#[test]
+fn test_entry_skip_children() {
+    struct SkipEntries {
+        entries: usize,
+        ports: usize,
+    }
+
+    impl Visitor for SkipEntries {
+        type Break = ();
+
+        fn visit(&mut self, item: &dyn Any, _event: Event) -> ControlFlow<(), Children> {
+            if item.is::<Port>() {
+                self.ports += 1;
+            }
+            ControlFlow::Continue(Children::Visit)
+        }
+
+        fn visit_entry(
+            &mut self,
+            _entry: MapEntry<'_>,
+            event: Event,
+            _ancestors: Ancestors<'_>,
+        ) -> ControlFlow<(), Children> {
+            if event == Event::Exit {
+                self.entries += 1;
+            }
+            ControlFlow::Continue(Children::Skip)
+        }
+    }
+
+    let host = Host {
+        services: vec![
+            (Port(22), Service("ssh".into())),
+            (Port(80), Service("http".into())),
+        ]
+        .into_iter()
+        .collect(),
+    };
+    let mut visitor = SkipEntries {
+        entries: 0,
+        ports: 0,
+    };
+    let _ = host.drive(&mut visitor);
+    // Skipped entries are still exited
+    assert_eq!((visitor.entries, visitor.ports), (2, 0));
+}
+
+#[test]
+fn test_entry_downcast() {
+    #[derive(Drive)]
+    struct Router {
+        #[drive(entries)]
+        services: BTreeMap<Port, Service>,
+        #[drive(entries)]
+        forwards: HashMap<Port, Port>,
+    }
+
+    struct Forwards(Vec<(u16, u16)>);
+
+    impl Visitor for Forwards {
+        type Break = ();
+
+        fn visit(&mut self, _item: &dyn Any, _event: Event) -> ControlFlow<(), Children> {
+            ControlFlow::Continue(Children::Visit)
+        }
+
+        fn visit_entry(
+            &mut self,
+            entry: MapEntry<'_>,
+            event: Event,
+            _ancestors: Ancestors<'_>,
+        ) -> ControlFlow<(), Children> {
+            if let (Some(entry), Event::Enter) = (entry.downcast::<Port, Port>(), event) {
+                self.0.push((entry.key().0, entry.value().0));
+            }
+            ControlFlow::Continue(Children::Visit)
+        }
+    }
+
+    let router = Router {
+        services: vec![(Port(22), Service("ssh".into()))]
+            .into_iter()
+            .collect(),
+        forwards: vec![(Port(8080), Port(80))].into_iter().collect(),
+    };
+    let mut forwards = Forwards(Vec::new());
+    let _ = router.drive(&mut forwards);
+    assert_eq!(forwards.0, [(8080, 80)]);
+}
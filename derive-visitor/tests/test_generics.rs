@@ -0,0 +1,171 @@
+use std::{collections::BTreeMap, marker::PhantomData, rc::Rc, sync::Arc};
+
+use derive_visitor::{visitor_enter_fn, Drive, DriveMut, Unshare, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut, Clone)]
+struct Leaf(#[drive(skip)] u32);
+
+// No bounds on the type parameters
+#[derive(Drive, DriveMut)]
+struct Node<T> {
+    value: T,
+    children: Vec<Node<T>>,
+}
+
+// Only `T` has to implement `Drive`
+#[derive(Drive, DriveMut)]
+struct Tagged<T, U> {
+    value: T,
+    #[drive(skip)]
+    tag: U,
+    marker: PhantomData<U>,
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Leaf(enter))]
+struct Sum(u32);
+
+impl Sum {
+    fn enter_leaf(&mut self, leaf: &Leaf) {
+        self.0 += leaf.0;
+    }
+}
+
+fn node(value: u32, children: Vec<Node<Leaf>>) -> Node<Leaf> {
+    Node {
+        value: Leaf(value),
+        children,
+    }
+}
+
+#[test]
+fn test_inferred_bounds() {
+    let mut tree = node(1, vec![node(2, vec![]), node(3, vec![])]);
+    let mut visitor = Sum::default();
+    let _ = tree.drive(&mut visitor);
+    let _ = tree.drive_mut(&mut visitor);
+    assert_eq!(visitor.0, 12);
+
+    let mut tagged = Tagged {
+        value: Leaf(4),
+        tag: String::new(),
+        marker: PhantomData,
+    };
+    let mut visitor = Sum::default();
+    let _ = tagged.drive(&mut visitor);
+    let _ = tagged.drive_mut(&mut visitor);
+    assert_eq!((visitor.0, tagged.tag.as_str()), (8, ""));
+}
+
+// Mutable traversals only drive the visitor through the values, so `K` doesn't have to implement `DriveMut`
+#[derive(Drive, DriveMut)]
+struct Index<K: Ord, T> {
+    #[drive(entries)]
+    entries: BTreeMap<K, T>,
+}
+
+#[derive(Drive, PartialEq, Eq, PartialOrd, Ord)]
+struct Key(#[drive(skip)] u32);
+
+#[test]
+fn test_inferred_entries_bounds() {
+    let mut index = Index {
+        entries: vec![(Key(1), Leaf(2)), (Key(3), Leaf(4))]
+            .into_iter()
+            .collect(),
+    };
+    let mut visitor = Sum::default();
+    let _ = index.drive(&mut visitor);
+    let _ = index.drive_mut(&mut visitor);
+    assert_eq!(visitor.0, 12);
+}
+
+trait Language: 'static {
+    type Item;
+}
+
+// Deliberately doesn't implement `Drive`
+struct Toy;
+
+impl Language for Toy {
+    type Item = Leaf;
+}
+
+// Only `L::Item` and `<L as Language>::Item` have to implement `Drive`, not `L`
+#[derive(Drive, DriveMut)]
+struct Program<L: Language> {
+    items: Vec<L::Item>,
+    main: Option<<L as Language>::Item>,
+    language: std::marker::PhantomData<L>,
+}
+
+#[test]
+fn test_inferred_associated_type_bounds() {
+    let mut program = Program::<Toy> {
+        items: vec![Leaf(1), Leaf(2)],
+        main: Some(Leaf(3)),
+        language: PhantomData,
+    };
+    let mut visitor = Sum::default();
+    let _ = program.drive(&mut visitor);
+    let _ = program.drive_mut(&mut visitor);
+    assert_eq!(visitor.0, 12);
+}
+
+// The inferred `T: DriveMut` isn't enough to drive a visitor through `Rc<T>`
+#[derive(Drive, DriveMut)]
+#[drive(bound(drive = "T: Drive", drive_mut = "T: DriveMut + Unshare"))]
+struct Cached<T> {
+    value: Rc<T>,
+}
+
+#[test]
+fn test_custom_bounds() {
+    #[derive(Drive, DriveMut, Clone)]
+    #[drive(unshare = "clone")]
+    struct Shared(Leaf);
+
+    let mut cached = Cached {
+        value: Rc::new(Shared(Leaf(1))),
+    };
+    let mut visitor = Sum::default();
+    let _ = cached.drive(&mut visitor);
+    let _ = cached.drive_mut(&mut visitor);
+    assert_eq!(visitor.0, 2);
+}
+
+#[test]
+fn test_custom_driver_bounds() {
+    // The custom driver doesn't require `T: Drive`
+    #[derive(Drive)]
+    struct Labeled<T> {
+        #[drive(with = "drive_label")]
+        label: T,
+    }
+
+    fn drive_label<T, V: Visitor>(_label: &T, _visitor: &mut V) -> std::ops::ControlFlow<V::Break> {
+        std::ops::ControlFlow::Continue(())
+    }
+
+    struct NotDrive;
+
+    let mut visited = 0;
+    let _ = Labeled { label: NotDrive }.drive(&mut visitor_enter_fn(|_: &Labeled<NotDrive>| {
+        visited += 1;
+    }));
+    assert_eq!(visited, 1);
+}
+
+#[test]
+fn test_unshare_bounds() {
+    #[derive(Drive, DriveMut, Clone)]
+    #[drive(unshare = "clone")]
+    struct Shared<T>(T);
+
+    let first = Arc::new(Shared(Leaf(1)));
+    let mut second = first.clone();
+    let mut visitor = Sum::default();
+    let _ = second.drive_mut(&mut visitor);
+    assert_eq!(visitor.0, 1);
+    assert!(!Arc::ptr_eq(&first, &second));
+}
@@ -0,0 +1,165 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{Drive, DriveMut, Event, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Statement(#[drive(skip)] u32);
+
+#[derive(Drive, DriveMut)]
+#[drive(
+    on_enter_ref = "Scope::open",
+    on_exit_ref = "Scope::close",
+    on_enter_mut = "Scope::open_mut",
+    on_exit_mut = "Scope::close_mut"
+)]
+struct Block {
+    statements: Vec<Statement>,
+}
+
+// Synthetic item emitted around every block
+struct Scope;
+
+impl Scope {
+    fn open<V: Visitor>(_block: &Block, visitor: &mut V) -> ControlFlow<V::Break> {
+        visitor.visit(&Scope, Event::Enter)?;
+        ControlFlow::Continue(())
+    }
+
+    fn close<V: Visitor>(_block: &Block, visitor: &mut V) -> ControlFlow<V::Break> {
+        visitor.visit(&Scope, Event::Exit)?;
+        ControlFlow::Continue(())
+    }
+
+    fn open_mut<V: VisitorMut>(_block: &mut Block, visitor: &mut V) -> ControlFlow<V::Break> {
+        visitor.visit(&mut Scope, Event::Enter)?;
+        ControlFlow::Continue(())
+    }
+
+    fn close_mut<V: VisitorMut>(block: &mut Block, visitor: &mut V) -> ControlFlow<V::Break> {
+        block.statements.push(Statement(0));
+        visitor.visit(&mut Scope, Event::Exit)?;
+        ControlFlow::Continue(())
+    }
+}
+
+// Hooks run even if the type isn't visited itself
+#[derive(Drive)]
+#[drive(skip, on_enter = "Item::open", on_exit = "Item::close")]
+struct Item(Block);
+
+impl Item {
+    fn open<V: Visitor>(item: &Item, visitor: &mut V) -> ControlFlow<V::Break> {
+        Scope::open(&item.0, visitor)
+    }
+
+    fn close<V: Visitor>(item: &Item, visitor: &mut V) -> ControlFlow<V::Break> {
+        Scope::close(&item.0, visitor)
+    }
+}
+
+// Checks that blocks are entered and exited inside of their scope
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Scope, Block, Statement(enter))]
+struct Scopes {
+    open: usize,
+    deepest: usize,
+    opened: usize,
+    blocks: usize,
+    statements: usize,
+}
+
+impl Scopes {
+    fn enter_scope(&mut self, _scope: &Scope) {
+        self.open += 1;
+        self.opened += 1;
+        self.deepest = self.deepest.max(self.open);
+    }
+
+    fn exit_scope(&mut self, _scope: &Scope) {
+        self.open -= 1;
+    }
+
+    fn enter_block(&mut self, _block: &Block) {
+        assert!(self.open > 0);
+    }
+
+    fn exit_block(&mut self, _block: &Block) {
+        assert!(self.open > 0);
+        self.blocks += 1;
+    }
+
+    fn enter_statement(&mut self, _statement: &Statement) {
+        self.statements += 1;
+    }
+}
+
+#[test]
+fn test_hooks() {
+    let block = Block {
+        statements: vec![Statement(1), Statement(2)],
+    };
+    let mut scopes = Scopes::default();
+    let _ = block.drive(&mut scopes);
+    assert_eq!((scopes.opened, scopes.open), (1, 0));
+    assert_eq!((scopes.blocks, scopes.statements), (1, 2));
+}
+
+#[test]
+fn test_hooks_after_break() {
+    #[derive(Default, Visitor)]
+    #[visitor(Scope, Block(exit), Statement(enter), break = "u32")]
+    struct FirstStatement {
+        open: usize,
+        blocks: usize,
+    }
+
+    impl FirstStatement {
+        fn enter_scope(&mut self, _scope: &Scope) {
+            self.open += 1;
+        }
+
+        fn exit_scope(&mut self, _scope: &Scope) {
+            self.open -= 1;
+        }
+
+        fn exit_block(&mut self, _block: &Block) {
+            self.blocks += 1;
+        }
+
+        fn enter_statement(&mut self, statement: &Statement) -> ControlFlow<u32> {
+            ControlFlow::Break(statement.0)
+        }
+    }
+
+    let block = Block {
+        statements: vec![Statement(1), Statement(2)],
+    };
+    let mut visitor = FirstStatement::default();
+    assert_eq!(block.drive(&mut visitor), ControlFlow::Break(1));
+    // The exit hook still runs, but the block isn't exited
+    assert_eq!((visitor.open, visitor.blocks), (0, 0));
+}
+
+#[test]
+fn test_hooks_mut() {
+    let mut block = Block {
+        statements: vec![Statement(1), Statement(2)],
+    };
+    let mut scopes = Scopes::default();
+    let _ = block.drive_mut(&mut scopes);
+    assert_eq!((scopes.opened, scopes.open), (1, 0));
+    // The statement added by the exit hook isn't visited
+    assert_eq!(scopes.statements, 2);
+    assert_eq!(block.statements.len(), 3);
+}
+
+#[test]
+fn test_hooks_of_skipped_type() {
+    let item = Item(Block {
+        statements: vec![Statement(1)],
+    });
+    let mut scopes = Scopes::default();
+    let _ = item.drive(&mut scopes);
+    assert_eq!((scopes.opened, scopes.deepest, scopes.open), (2, 2, 0));
+    assert_eq!(scopes.blocks, 1);
+}
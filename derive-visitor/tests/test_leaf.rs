@@ -0,0 +1,118 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{Drive, DriveMut, Visitor, VisitorMut};
+
+// Deliberately doesn't implement `Drive`
+#[derive(Debug, PartialEq)]
+struct Timestamp(u64);
+
+#[derive(Drive, DriveMut)]
+struct File {
+    #[drive(leaf)]
+    name: String,
+    #[drive(leaf)]
+    modified: Timestamp,
+    #[drive(skip)]
+    size: u64,
+}
+
+#[derive(Drive, DriveMut)]
+enum Entry {
+    File(File),
+    Link(#[drive(leaf)] String),
+}
+
+#[derive(VisitorMut)]
+#[visitor(String(enter))]
+struct Upper;
+
+impl Upper {
+    fn enter_string(&mut self, name: &mut String) {
+        *name = name.to_uppercase();
+    }
+}
+
+#[test]
+fn test_leaf() {
+    #[derive(Default, Visitor)]
+    #[visitor(String(enter), Timestamp(enter))]
+    struct Names {
+        names: Vec<String>,
+        timestamps: Vec<u64>,
+    }
+
+    impl Names {
+        fn enter_string(&mut self, name: &str) {
+            self.names.push(name.to_string());
+        }
+        fn enter_timestamp(&mut self, timestamp: &Timestamp) {
+            self.timestamps.push(timestamp.0);
+        }
+    }
+
+    let entries = vec![
+        Entry::File(File {
+            name: "notes.txt".to_string(),
+            modified: Timestamp(42),
+            size: 3,
+        }),
+        Entry::Link("latest".to_string()),
+    ];
+    let mut names = Names::default();
+    let _ = entries.drive(&mut names);
+    assert_eq!(names.names, ["notes.txt", "latest"]);
+    assert_eq!(names.timestamps, [42]);
+}
+
+#[test]
+fn test_leaf_break() {
+    #[derive(Default, Visitor)]
+    #[visitor(String(exit), Timestamp(enter), break = "String")]
+    struct FirstName {
+        timestamps: usize,
+    }
+
+    impl FirstName {
+        fn exit_string(&mut self, name: &str) -> ControlFlow<String> {
+            ControlFlow::Break(name.to_string())
+        }
+        fn enter_timestamp(&mut self, _timestamp: &Timestamp) {
+            self.timestamps += 1;
+        }
+    }
+
+    let file = File {
+        name: "notes.txt".to_string(),
+        modified: Timestamp(42),
+        size: 3,
+    };
+    let mut visitor = FirstName::default();
+    assert_eq!(
+        file.drive(&mut visitor),
+        ControlFlow::Break("notes.txt".to_string())
+    );
+    // `modified` comes after the name
+    assert_eq!(visitor.timestamps, 0);
+}
+
+#[test]
+fn test_leaf_mut() {
+    let mut entries = vec![
+        Entry::File(File {
+            name: "notes.txt".to_string(),
+            modified: Timestamp(42),
+            size: 3,
+        }),
+        Entry::Link("latest".to_string()),
+    ];
+    let _ = entries.drive_mut(&mut Upper);
+    match &entries[..] {
+        [Entry::File(file), Entry::Link(link)] => {
+            assert_eq!(file.name, "NOTES.TXT");
+            assert_eq!(file.modified, Timestamp(42));
+            assert_eq!(file.size, 3);
+            assert_eq!(link, "LATEST");
+        }
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,119 @@
+use derive_visitor::{Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Ident(#[drive(skip)] &'static str);
+
+#[derive(Drive, DriveMut)]
+enum Expr {
+    Ident(Ident),
+    Let(Box<Let>),
+    Call(Box<Call>),
+}
+
+#[derive(Drive, DriveMut)]
+struct Let {
+    // The value is evaluated before the name is bound, and the body afterwards
+    #[drive(order = 1)]
+    name: Ident,
+    ty: Ident,
+    #[drive(order = 2)]
+    body: Expr,
+    value: Expr,
+}
+
+// Arguments are evaluated right to left, after the callee
+#[derive(Drive, DriveMut)]
+#[drive(reverse)]
+struct Call {
+    #[drive(order = 1)]
+    callee: Ident,
+    first: Expr,
+    second: Expr,
+}
+
+#[derive(Drive, DriveMut)]
+#[drive(reverse)]
+enum Stmt {
+    Assign(Ident, Expr),
+    Let(Let),
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Ident(enter))]
+struct Evaluation {
+    idents: Vec<&'static str>,
+}
+
+impl Evaluation {
+    fn enter_ident(&mut self, ident: &Ident) {
+        self.idents.push(ident.0);
+    }
+}
+
+fn ident(name: &'static str) -> Expr {
+    Expr::Ident(Ident(name))
+}
+
+// let x: i32 = a; x
+fn let_x() -> Let {
+    Let {
+        name: Ident("x"),
+        ty: Ident("i32"),
+        body: ident("x"),
+        value: ident("a"),
+    }
+}
+
+#[test]
+fn test_order() {
+    let mut evaluation = Evaluation::default();
+    let _ = let_x().drive(&mut evaluation);
+    assert_eq!(evaluation.idents, ["i32", "a", "x", "x"]);
+
+    let mut evaluation = Evaluation::default();
+    let _ = let_x().drive_mut(&mut evaluation);
+    assert_eq!(evaluation.idents, ["i32", "a", "x", "x"]);
+}
+
+#[test]
+fn test_order_reverse() {
+    // f(g(a, b), c)
+    let call = Call {
+        callee: Ident("f"),
+        first: Expr::Call(Box::new(Call {
+            callee: Ident("g"),
+            first: ident("a"),
+            second: ident("b"),
+        })),
+        second: ident("c"),
+    };
+    let mut evaluation = Evaluation::default();
+    let _ = call.drive(&mut evaluation);
+    assert_eq!(evaluation.idents, ["f", "c", "g", "b", "a"]);
+}
+
+#[test]
+fn test_order_reverse_variant() {
+    // y = f(x); let x: i32 = a; x
+    let mut stmts = vec![
+        Stmt::Assign(
+            Ident("y"),
+            Expr::Call(Box::new(Call {
+                callee: Ident("f"),
+                first: ident("x"),
+                second: Expr::Let(Box::new(let_x())),
+            })),
+        ),
+        Stmt::Let(let_x()),
+    ];
+    let mut evaluation = Evaluation::default();
+    let _ = stmts.drive_mut(&mut evaluation);
+    assert_eq!(
+        evaluation.idents,
+        [
+            "f", "i32", "a", "x", "x", "x", "y",
+            // Only the variant's fields are reversed
+            "i32", "a", "x", "x"
+        ]
+    );
+}
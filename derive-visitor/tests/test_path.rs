@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use derive_visitor::{Drive, DriveMut, Path, PathSegment, Visitor, VisitorMut};
+
+#[derive(Default, Drive, DriveMut)]
+struct Root {
+    items: Vec<Item>,
+    by_name: BTreeMap<Name, Leaf>,
+    pair: (Leaf, Leaf),
+    maybe: Option<Leaf>,
+    wrapped: Wrapper,
+    r#type: Leaf,
+}
+
+#[derive(Drive, DriveMut)]
+enum Item {
+    Leaf(Leaf),
+    Pair(Leaf, Leaf),
+    Named { first: Leaf },
+}
+
+#[derive(Default, Drive, DriveMut)]
+struct Wrapper(Vec<Leaf>);
+
+#[derive(Default, Drive, DriveMut)]
+struct Leaf;
+
+#[derive(Drive, PartialEq, Eq, PartialOrd, Ord)]
+struct Name(#[drive(skip)] &'static str);
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Leaf(enter), path = "path")]
+struct LeafPaths {
+    path: Path,
+    found: Vec<String>,
+}
+
+impl LeafPaths {
+    fn enter_leaf(&mut self, _leaf: &Leaf) {
+        self.found.push(self.path.to_string());
+    }
+}
+
+fn example() -> Root {
+    Root {
+        items: vec![
+            Item::Leaf(Leaf),
+            Item::Pair(Leaf, Leaf),
+            Item::Named { first: Leaf },
+        ],
+        by_name: vec![(Name("main"), Leaf)].into_iter().collect(),
+        maybe: Some(Leaf),
+        wrapped: Wrapper(vec![Leaf]),
+        ..Root::default()
+    }
+}
+
+const EXPECTED: [&str; 10] = [
+    "items[0].Leaf",
+    "items[1].Pair.0",
+    "items[1].Pair.1",
+    "items[2].Named.first",
+    "by_name[..]",
+    "pair.0",
+    "pair.1",
+    "maybe",
+    "wrapped[0]",
+    "type",
+];
+
+#[test]
+fn test_path() {
+    let mut visitor = LeafPaths::default();
+    let _ = example().drive(&mut visitor);
+    assert_eq!(visitor.found, EXPECTED);
+    assert!(visitor.path.is_empty());
+}
+
+#[test]
+fn test_path_mut() {
+    let mut visitor = LeafPaths::default();
+    let _ = example().drive_mut(&mut visitor);
+    assert_eq!(visitor.found, EXPECTED);
+    assert!(visitor.path.is_empty());
+}
+
+#[test]
+fn test_path_keys() {
+    let mut path = Path::new();
+    path.push(PathSegment::Field("by_name"));
+    path.push(PathSegment::Key(&"main".to_string()));
+    assert_eq!(path.as_str(), "by_name[\"main\"]");
+    path.pop();
+    path.push(PathSegment::Key(&42_u8));
+    assert_eq!(path.as_str(), "by_name[42]");
+    path.pop();
+    path.push(PathSegment::Key(&Name("main")));
+    assert_eq!(path.as_str(), "by_name[..]");
+    assert_eq!(path.len(), 2);
+}
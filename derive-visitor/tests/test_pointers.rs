@@ -0,0 +1,98 @@
+use std::{
+    borrow::Cow,
+    pin::Pin,
+    rc::{self, Rc},
+    sync::{self, Arc},
+};
+
+use derive_visitor::{visitor_enter_fn, visitor_enter_fn_mut, Drive, DriveMut};
+
+#[derive(Drive)]
+struct Graph {
+    root: Rc<Leaf>,
+    #[drive(upgrade)]
+    parent: rc::Weak<Leaf>,
+    #[drive(upgrade)]
+    dangling: rc::Weak<Leaf>,
+    #[drive(upgrade)]
+    remote: sync::Weak<Leaf>,
+    sibling: rc::Weak<Leaf>,
+}
+
+#[derive(Clone, Drive, DriveMut)]
+struct Leaf(#[drive(skip)] &'static str);
+
+static SHARED: Leaf = Leaf("static");
+
+#[derive(Drive, DriveMut)]
+struct Document {
+    borrowed: Cow<'static, Leaf>,
+    owned: Cow<'static, Leaf>,
+    pinned: Pin<Box<Leaf>>,
+}
+
+fn names<T: Drive>(value: &T) -> Vec<&'static str> {
+    let mut names = Vec::new();
+    let _ = value.drive(&mut visitor_enter_fn(|leaf: &Leaf| names.push(leaf.0)));
+    names
+}
+
+#[test]
+fn test_rc_and_weak() {
+    let parent = Rc::new(Leaf("parent"));
+    let remote = Arc::new(Leaf("remote"));
+    let graph = Graph {
+        root: Rc::new(Leaf("root")),
+        parent: Rc::downgrade(&parent),
+        dangling: Rc::downgrade(&Rc::new(Leaf("dropped"))),
+        remote: Arc::downgrade(&remote),
+        sibling: Rc::downgrade(&parent),
+    };
+    assert_eq!(names(&graph), ["root", "parent", "remote"]);
+
+    drop(parent);
+    drop(remote);
+    assert_eq!(names(&graph), ["root"]);
+}
+
+#[derive(Drive)]
+struct Tree {
+    #[drive(skip)]
+    name: &'static str,
+    children: Vec<Rc<Tree>>,
+    parent: rc::Weak<Tree>,
+}
+
+#[test]
+fn test_weak_parent() {
+    let root = Rc::new_cyclic(|root| Tree {
+        name: "root",
+        children: vec![Rc::new(Tree {
+            name: "child",
+            children: vec![],
+            parent: root.clone(),
+        })],
+        parent: rc::Weak::new(),
+    });
+
+    let mut names = Vec::new();
+    let _ = root.drive(&mut visitor_enter_fn(|tree: &Tree| names.push(tree.name)));
+    assert_eq!(names, ["root", "child"]);
+}
+
+#[test]
+fn test_cow_and_pin() {
+    let mut document = Document {
+        borrowed: Cow::Borrowed(&SHARED),
+        owned: Cow::Owned(Leaf("owned")),
+        pinned: Box::pin(Leaf("pinned")),
+    };
+    assert_eq!(names(&document), ["static", "owned", "pinned"]);
+
+    let _ = document.drive_mut(&mut visitor_enter_fn_mut(|leaf: &mut Leaf| {
+        leaf.0 = "changed";
+    }));
+    assert!(matches!(document.borrowed, Cow::Owned(_)));
+    assert_eq!(names(&document), ["changed", "changed", "changed"]);
+    assert_eq!(SHARED.0, "static");
+}
@@ -0,0 +1,134 @@
+use std::{
+    any::Any,
+    ops::ControlFlow,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use derive_visitor::{
+    visitor_enter_fn, Drive, DriveMut, Event, PoisonPolicy, Poisoned, TryVisitor, Visitor,
+    VisitorMut,
+};
+
+#[derive(Drive, DriveMut)]
+struct Registry {
+    entries: Vec<Arc<Mutex<Entry>>>,
+    config: RwLock<Entry>,
+}
+
+#[derive(Drive, DriveMut)]
+struct Entry(#[drive(skip)] u32);
+
+fn poison<T>(mutex: &Mutex<T>) {
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _lock = mutex.lock().unwrap();
+        panic!("poisoning");
+    }));
+    assert!(mutex.is_poisoned());
+}
+
+fn example() -> Registry {
+    let registry = Registry {
+        entries: vec![
+            Arc::new(Mutex::new(Entry(1))),
+            Arc::new(Mutex::new(Entry(2))),
+        ],
+        config: RwLock::new(Entry(3)),
+    };
+    poison(&registry.entries[0]);
+    registry
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Entry(enter), poisoned = "recover")]
+struct Recover(Vec<u32>);
+
+impl Recover {
+    fn enter_entry(&mut self, entry: &Entry) {
+        self.0.push(entry.0);
+    }
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Entry(enter), poisoned = "skip")]
+struct Skip(Vec<u32>);
+
+impl Skip {
+    fn enter_entry(&mut self, entry: &Entry) {
+        self.0.push(entry.0);
+    }
+}
+
+#[test]
+fn test_poison_policies() {
+    let mut registry = example();
+
+    let mut recover = Recover::default();
+    let _ = registry.drive(&mut recover);
+    assert_eq!(recover.0, [1, 2, 3]);
+
+    let mut skip = Skip::default();
+    let _ = registry.drive_mut(&mut skip);
+    assert_eq!(skip.0, [2, 3]);
+
+    let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _ = registry.drive(&mut visitor_enter_fn(|_: &Entry| {}));
+    }));
+    assert!(panicked.is_err());
+}
+
+#[test]
+fn test_poison_break() {
+    #[derive(Debug, PartialEq)]
+    enum Error {
+        Poisoned,
+    }
+
+    impl From<Poisoned> for Error {
+        fn from(_: Poisoned) -> Self {
+            Error::Poisoned
+        }
+    }
+
+    #[derive(Visitor)]
+    #[visitor(Entry(enter), break = "Error", poisoned = "break")]
+    struct Report;
+
+    impl Report {
+        fn enter_entry(&mut self, _entry: &Entry) {}
+    }
+
+    assert_eq!(
+        example().drive(&mut Report),
+        ControlFlow::Break(Error::Poisoned)
+    );
+
+    struct TryReport;
+
+    impl TryVisitor for TryReport {
+        type Error = &'static str;
+
+        fn visit(&mut self, _item: &dyn Any, _event: Event) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn poisoned(&mut self) -> Result<PoisonPolicy, Self::Error> {
+            Err("poisoned")
+        }
+    }
+
+    assert_eq!(example().try_drive(&mut TryReport), Err("poisoned"));
+}
+
+#[test]
+fn test_drive_mut_without_locking() {
+    let mut mutex = Mutex::new(Entry(1));
+    let mut recover = Recover::default();
+    poison(&mutex);
+    let _ = mutex.drive_mut(&mut recover);
+    assert_eq!(recover.0, [1]);
+
+    let mut lock = RwLock::new(Entry(2));
+    let _ = lock.drive_mut(&mut recover);
+    assert_eq!(recover.0, [1, 2]);
+}
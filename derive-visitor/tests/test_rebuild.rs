@@ -0,0 +1,194 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    ops::ControlFlow,
+};
+
+use derive_visitor::{
+    visitor_enter_fn_mut, Children, DriveMut, Event, Path, PathSegment, VisitorMut,
+};
+
+#[derive(DriveMut, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+struct Name(#[drive(skip)] String);
+
+#[derive(DriveMut, Debug)]
+struct Score(#[drive(skip)] u32);
+
+#[derive(DriveMut)]
+struct Registry {
+    #[drive(rebuild)]
+    names: BTreeSet<Name>,
+    #[drive(rebuild)]
+    scores: HashMap<Name, Score>,
+    #[drive(rebuild)]
+    ranking: BinaryHeap<Name>,
+}
+
+fn name(name: &str) -> Name {
+    Name(name.to_string())
+}
+
+fn lowercase(name: &mut Name) {
+    name.0.make_ascii_lowercase();
+}
+
+#[test]
+fn test_rebuild_keys_and_elements() {
+    let mut registry = Registry {
+        names: vec![name("B"), name("A")].into_iter().collect(),
+        scores: vec![(name("X"), Score(1))].into_iter().collect(),
+        ranking: vec![name("C"), name("D")].into_iter().collect(),
+    };
+    let _ = registry.drive_mut(&mut visitor_enter_fn_mut(lowercase));
+
+    assert_eq!(
+        registry.names.into_iter().collect::<Vec<_>>(),
+        [name("a"), name("b")]
+    );
+    assert_eq!(registry.scores[&name("x")].0, 1);
+    assert_eq!(registry.ranking.into_sorted_vec(), [name("c"), name("d")]);
+}
+
+#[test]
+fn test_rebuild_collisions_keep_first() {
+    let mut scores: BTreeMap<Name, Score> = vec![(name("A"), Score(1)), (name("a"), Score(2))]
+        .into_iter()
+        .collect();
+    let mut names: BTreeSet<Name> = vec![name("A"), name("a")].into_iter().collect();
+
+    let mut visitor = visitor_enter_fn_mut(lowercase);
+    let _ = derive_visitor::DriveRebuild::drive_rebuild(&mut scores, &mut visitor);
+    let _ = derive_visitor::DriveRebuild::drive_rebuild(&mut names, &mut visitor);
+
+    assert_eq!(scores.len(), 1);
+    assert_eq!(scores[&name("a")].0, 1);
+    assert_eq!(names.into_iter().collect::<Vec<_>>(), [name("a")]);
+}
+
+#[test]
+fn test_rebuild_hash_set_collisions_keep_first() {
+    // Equal regardless of the id, which tells the colliding elements apart
+    #[derive(DriveMut, Debug)]
+    struct Tagged(#[drive(skip)] String, #[drive(skip)] u8);
+
+    impl PartialEq for Tagged {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Tagged {}
+
+    impl Hash for Tagged {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    let mut tags: HashSet<Tagged> = vec![
+        Tagged("A".into(), 1),
+        Tagged("a".into(), 2),
+        Tagged("B".into(), 3),
+    ]
+    .into_iter()
+    .collect();
+
+    let mut order = Vec::new();
+    let _ = derive_visitor::DriveRebuild::drive_rebuild(
+        &mut tags,
+        &mut visitor_enter_fn_mut(|tag: &mut Tagged| {
+            order.push(tag.1);
+            tag.0.make_ascii_lowercase();
+        }),
+    );
+
+    let first = *order.iter().find(|&&id| id != 3).unwrap();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags.get(&Tagged("a".into(), 0)).unwrap().1, first);
+}
+
+#[test]
+fn test_rebuild_binary_heap_keeps_duplicates() {
+    let mut ranking: BinaryHeap<Name> = vec![name("A"), name("a")].into_iter().collect();
+
+    let mut visitor = visitor_enter_fn_mut(lowercase);
+    let _ = derive_visitor::DriveRebuild::drive_rebuild(&mut ranking, &mut visitor);
+
+    assert_eq!(ranking.into_sorted_vec(), [name("a"), name("a")]);
+}
+
+#[test]
+fn test_rebuild_after_break() {
+    struct Rename(usize);
+
+    impl VisitorMut for Rename {
+        type Break = ();
+
+        fn visit(
+            &mut self,
+            item: &mut dyn std::any::Any,
+            event: Event,
+        ) -> ControlFlow<(), Children> {
+            if let (Some(name), Event::Enter) = (item.downcast_mut::<Name>(), event) {
+                if self.0 == 0 {
+                    return ControlFlow::Break(());
+                }
+                self.0 -= 1;
+                lowercase(name);
+            }
+            ControlFlow::Continue(Children::Visit)
+        }
+    }
+
+    let mut names: BTreeSet<Name> = vec![name("A"), name("B"), name("C")].into_iter().collect();
+    let flow = derive_visitor::DriveRebuild::drive_rebuild(&mut names, &mut Rename(2));
+
+    assert_eq!(flow, ControlFlow::Break(()));
+    assert_eq!(
+        names.into_iter().collect::<Vec<_>>(),
+        [name("C"), name("a"), name("b")]
+    );
+}
+
+#[test]
+fn test_rebuild_path() {
+    #[derive(Default)]
+    struct Paths {
+        path: Path,
+        paths: Vec<String>,
+    }
+
+    impl VisitorMut for Paths {
+        type Break = ();
+
+        fn visit(
+            &mut self,
+            _item: &mut dyn std::any::Any,
+            event: Event,
+        ) -> ControlFlow<(), Children> {
+            if event == Event::Enter {
+                self.paths.push(self.path.to_string());
+            }
+            ControlFlow::Continue(Children::Visit)
+        }
+
+        fn push_path_segment(&mut self, segment: PathSegment<'_>) {
+            self.path.push(segment);
+        }
+
+        fn pop_path_segment(&mut self) {
+            self.path.pop();
+        }
+    }
+
+    #[derive(DriveMut, PartialEq, Eq, PartialOrd, Ord)]
+    struct Id(#[drive(skip)] u8);
+
+    let mut scores: BTreeMap<Id, Score> = vec![(Id(1), Score(1)), (Id(2), Score(2))]
+        .into_iter()
+        .collect();
+    let mut visitor = Paths::default();
+    let _ = derive_visitor::DriveRebuild::drive_rebuild(&mut scores, &mut visitor);
+    // Keys are reported under the entry's position, values under the key
+    assert_eq!(visitor.paths, ["[0]", "[..]", "[1]", "[..]"]);
+}
@@ -0,0 +1,151 @@
+use std::{
+    rc::{Rc, Weak},
+    sync::{Arc, Mutex},
+};
+
+use derive_visitor::{
+    visitor_enter_fn, Allocation, Allocations, Drive, DriveMut, Revisit, Visitor, VisitorMut,
+};
+
+#[derive(Drive)]
+struct Dag {
+    #[drive(skip)]
+    name: &'static str,
+    children: Vec<Rc<Dag>>,
+}
+
+#[derive(Default, Visitor)]
+#[visitor(Dag(enter), shared = "allocations", revisit = "revisit")]
+struct DagNames {
+    allocations: Allocations,
+    names: Vec<&'static str>,
+    revisits: Vec<(Allocation, Revisit)>,
+}
+
+impl DagNames {
+    fn enter_dag(&mut self, dag: &Dag) {
+        self.names.push(dag.name);
+    }
+    fn revisit(&mut self, allocation: Allocation, revisit: Revisit) {
+        self.revisits.push((allocation, revisit));
+    }
+}
+
+#[test]
+fn test_shared_values() {
+    let shared = Rc::new(Dag {
+        name: "shared",
+        children: vec![],
+    });
+    let root = Dag {
+        name: "root",
+        children: vec![
+            Rc::new(Dag {
+                name: "a",
+                children: vec![shared.clone()],
+            }),
+            shared.clone(),
+        ],
+    };
+    let mut visitor = DagNames::default();
+    let _ = root.drive(&mut visitor);
+    assert_eq!(visitor.names, ["root", "a", "shared"]);
+    assert_eq!(
+        visitor.revisits,
+        [(Allocation::of(Rc::as_ptr(&shared)), Revisit::Shared)]
+    );
+    assert_eq!(visitor.allocations.len(), 2);
+
+    // Without tracking, shared values are visited once per pointer
+    let mut names = Vec::new();
+    let _ = root.drive(&mut visitor_enter_fn(|dag: &Dag| {
+        names.push(dag.name);
+    }));
+    assert_eq!(names, ["root", "a", "shared", "shared"]);
+}
+
+#[derive(Drive)]
+struct Tree {
+    #[drive(skip)]
+    name: &'static str,
+    children: Vec<Rc<Tree>>,
+    #[drive(upgrade)]
+    parent: Weak<Tree>,
+}
+
+#[test]
+fn test_weak_cycle() {
+    let root = Rc::new_cyclic(|root| Tree {
+        name: "root",
+        children: vec![Rc::new(Tree {
+            name: "child",
+            children: vec![],
+            parent: root.clone(),
+        })],
+        parent: Weak::new(),
+    });
+
+    #[derive(Default, Visitor)]
+    #[visitor(Tree(enter), shared = "allocations", revisit = "revisit")]
+    struct Names {
+        allocations: Allocations,
+        names: Vec<&'static str>,
+        cycles: usize,
+    }
+
+    impl Names {
+        fn enter_tree(&mut self, tree: &Tree) {
+            self.names.push(tree.name);
+        }
+        fn revisit(&mut self, _allocation: Allocation, revisit: Revisit) {
+            assert_eq!(revisit, Revisit::Cycle);
+            self.cycles += 1;
+        }
+    }
+
+    let mut visitor = Names::default();
+    let _ = root.drive(&mut visitor);
+    assert_eq!(visitor.names, ["root", "child"]);
+    assert_eq!(visitor.cycles, 1);
+}
+
+#[derive(Drive, DriveMut)]
+struct Link {
+    #[drive(skip)]
+    value: u32,
+    next: Option<Arc<Mutex<Link>>>,
+}
+
+#[test]
+fn test_mutex_cycle() {
+    let first = Arc::new(Mutex::new(Link {
+        value: 1,
+        next: None,
+    }));
+    let second = Arc::new(Mutex::new(Link {
+        value: 2,
+        next: Some(first.clone()),
+    }));
+    first.lock().unwrap().next = Some(second.clone());
+
+    #[derive(Default, VisitorMut)]
+    #[visitor(Link(enter), shared = "allocations")]
+    struct Double {
+        allocations: Allocations,
+    }
+
+    impl Double {
+        fn enter_link(&mut self, link: &mut Link) {
+            link.value *= 2;
+        }
+    }
+
+    // Would deadlock on the second lock of `first` without tracking
+    let mut start = first.clone();
+    let _ = start.drive_mut(&mut Double::default());
+    assert_eq!(first.lock().unwrap().value, 2);
+    assert_eq!(second.lock().unwrap().value, 4);
+
+    // Break the cycle so that the links can be dropped
+    first.lock().unwrap().next = None;
+}
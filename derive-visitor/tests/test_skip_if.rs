@@ -0,0 +1,126 @@
+use derive_visitor::{Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Ident(#[drive(skip)] String);
+
+#[derive(Drive, DriveMut)]
+struct Function {
+    #[drive(skip_if = "Function::is_anonymous")]
+    name: Ident,
+    #[drive(skip_if = "Function::is_extern")]
+    body: Block,
+    #[drive(skip)]
+    is_extern: bool,
+}
+
+impl Function {
+    fn is_anonymous(&self, name: &Ident) -> bool {
+        name.0.is_empty()
+    }
+
+    fn is_extern(&self, _body: &Block) -> bool {
+        self.is_extern
+    }
+}
+
+#[derive(Drive, DriveMut)]
+struct Block {
+    statements: Vec<Statement>,
+}
+
+#[derive(Drive, DriveMut)]
+enum Statement {
+    #[drive(skip_if = "Statement::is_unreachable")]
+    Expr(Ident, #[drive(skip)] bool),
+    Let {
+        #[drive(skip_if = "Statement::is_discarded")]
+        name: Ident,
+        value: Ident,
+    },
+}
+
+impl Statement {
+    fn is_unreachable(&self) -> bool {
+        matches!(self, Self::Expr(_, true))
+    }
+
+    fn is_discarded(&self, name: &Ident) -> bool {
+        name.0 == "_"
+    }
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Ident(enter))]
+struct IdentCollector {
+    idents: Vec<String>,
+}
+
+impl IdentCollector {
+    fn enter_ident(&mut self, ident: &Ident) {
+        self.idents.push(ident.0.clone());
+    }
+}
+
+#[test]
+fn test_skip_if_field() {
+    let extern_function = Function {
+        name: Ident("main".into()),
+        body: Block {
+            statements: vec![Statement::Expr(Ident("a".into()), false)],
+        },
+        is_extern: true,
+    };
+    let mut collector = IdentCollector::default();
+    let _ = extern_function.drive(&mut collector);
+    assert_eq!(collector.idents, ["main"]);
+
+    let anonymous_function = Function {
+        name: Ident(String::new()),
+        body: Block { statements: vec![] },
+        is_extern: true,
+    };
+    let mut collector = IdentCollector::default();
+    let _ = anonymous_function.drive(&mut collector);
+    assert!(collector.idents.is_empty());
+}
+
+#[test]
+fn test_skip_if_variant() {
+    let block = Block {
+        statements: vec![
+            Statement::Let {
+                name: Ident("_".into()),
+                value: Ident("a".into()),
+            },
+            Statement::Let {
+                name: Ident("b".into()),
+                value: Ident("c".into()),
+            },
+            Statement::Expr(Ident("d".into()), false),
+            Statement::Expr(Ident("e".into()), true),
+        ],
+    };
+    let mut collector = IdentCollector::default();
+    let _ = block.drive(&mut collector);
+    assert_eq!(collector.idents, ["a", "b", "c", "d"]);
+}
+
+#[test]
+fn test_skip_if_mut() {
+    let mut function = Function {
+        name: Ident(String::new()),
+        body: Block {
+            statements: vec![
+                Statement::Let {
+                    name: Ident("_".into()),
+                    value: Ident("a".into()),
+                },
+                Statement::Expr(Ident("b".into()), true),
+            ],
+        },
+        is_extern: false,
+    };
+    let mut collector = IdentCollector::default();
+    let _ = function.drive_mut(&mut collector);
+    assert_eq!(collector.idents, ["a"]);
+}
@@ -59,7 +59,7 @@ fn test_std_types() {
         vec_field: vec![1, 2, 3],
     };
     let mut test_visitor = TestVisitor::default();
-    top.drive(&mut test_visitor);
+    let _ = top.drive(&mut test_visitor);
     assert_eq!(
         test_visitor,
         TestVisitor {
@@ -0,0 +1,114 @@
+use std::{any::Any, collections::BTreeMap};
+
+use derive_visitor::{Drive, DriveMut, Event, TryVisitor, TryVisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Config {
+    sections: Vec<Section>,
+    fallback: Option<Section>,
+}
+
+#[derive(Drive, DriveMut)]
+struct Section {
+    #[drive(skip)]
+    name: String,
+    values: BTreeMap<Key, Value>,
+}
+
+#[derive(Drive, PartialEq, Eq, PartialOrd, Ord)]
+struct Key(#[drive(skip)] u32);
+
+#[derive(Drive, DriveMut)]
+struct Value(#[drive(skip)] i64);
+
+#[derive(Default)]
+struct NoNegatives {
+    checked: usize,
+}
+
+impl TryVisitor for NoNegatives {
+    type Error = i64;
+
+    fn visit(&mut self, item: &dyn Any, event: Event) -> Result<(), i64> {
+        if let (Some(Value(value)), Event::Enter) = (item.downcast_ref::<Value>(), event) {
+            if *value < 0 {
+                return Err(*value);
+            }
+            self.checked += 1;
+        }
+        Ok(())
+    }
+}
+
+fn section(name: &str, values: &[i64]) -> Section {
+    Section {
+        name: name.to_string(),
+        values: (0..)
+            .map(Key)
+            .zip(values.iter().copied().map(Value))
+            .collect(),
+    }
+}
+
+#[test]
+fn test_try_drive() {
+    let config = Config {
+        sections: vec![section("a", &[1, 2]), section("b", &[3])],
+        fallback: Some(section("c", &[4])),
+    };
+    let mut visitor = NoNegatives::default();
+    assert_eq!(config.try_drive(&mut visitor), Ok(()));
+    assert_eq!(visitor.checked, 4);
+}
+
+#[test]
+fn test_try_drive_error() {
+    let config = Config {
+        sections: vec![section("a", &[1, -2, 3]), section("b", &[-4])],
+        fallback: None,
+    };
+    let mut visitor = NoNegatives::default();
+    assert_eq!(config.try_drive(&mut visitor), Err(-2));
+    assert_eq!(visitor.checked, 1);
+    assert_eq!(
+        config.fallback.try_drive(&mut NoNegatives::default()),
+        Ok(())
+    );
+    assert_eq!(
+        config.sections[1]
+            .values
+            .try_drive(&mut NoNegatives::default()),
+        Err(-4)
+    );
+}
+
+#[test]
+fn test_try_drive_mut() {
+    struct Rename {
+        limit: usize,
+    }
+
+    impl TryVisitorMut for Rename {
+        type Error = String;
+
+        fn visit(&mut self, item: &mut dyn Any, event: Event) -> Result<(), String> {
+            if let (Some(section), Event::Enter) = (item.downcast_mut::<Section>(), event) {
+                if self.limit == 0 {
+                    return Err(section.name.clone());
+                }
+                section.name = section.name.to_uppercase();
+                self.limit -= 1;
+            }
+            Ok(())
+        }
+    }
+
+    let mut config = Config {
+        sections: vec![section("a", &[]), section("b", &[]), section("c", &[])],
+        fallback: None,
+    };
+    let result = config.try_drive_mut(&mut Rename { limit: 2 });
+    assert_eq!(result, Err("c".to_string()));
+    let names: Vec<_> = config.sections.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, ["A", "B", "c"]);
+}
@@ -0,0 +1,255 @@
+use std::{ops::ControlFlow, rc::Rc};
+
+use derive_visitor::{Allocations, Children, Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Module {
+    #[drive(skip)]
+    name: String,
+    #[drive(skip)]
+    generated: bool,
+    children: Vec<Module>,
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Module(enter))]
+struct Counter {
+    modules: usize,
+}
+
+impl Counter {
+    fn enter_module(&mut self, _module: &Module) {
+        self.modules += 1;
+    }
+}
+
+#[derive(Default, Visitor)]
+#[visitor(Module(enter))]
+struct Names {
+    skip_generated: bool,
+    names: Vec<String>,
+}
+
+impl Names {
+    fn enter_module(&mut self, module: &Module) -> Children {
+        self.names.push(module.name.clone());
+        if self.skip_generated && module.generated {
+            Children::Skip
+        } else {
+            Children::Visit
+        }
+    }
+}
+
+#[test]
+fn test_tuple_visitor() {
+    let root = Module {
+        name: "root".into(),
+        generated: false,
+        children: vec![
+            Module {
+                name: "a".into(),
+                generated: false,
+                children: vec![],
+            },
+            Module {
+                name: "b".into(),
+                generated: false,
+                children: vec![],
+            },
+        ],
+    };
+    let mut visitors = (Counter::default(), Names::default());
+    let _ = root.drive(&mut visitors);
+    assert_eq!(visitors.0.modules, 3);
+    assert_eq!(visitors.1.names, ["root", "a", "b"]);
+}
+
+#[test]
+fn test_tuple_visitor_skip() {
+    let root = Module {
+        name: "root".into(),
+        generated: false,
+        children: vec![
+            Module {
+                name: "gen".into(),
+                generated: true,
+                children: vec![Module {
+                    name: "hidden".into(),
+                    generated: false,
+                    children: vec![],
+                }],
+            },
+            Module {
+                name: "a".into(),
+                generated: false,
+                children: vec![],
+            },
+        ],
+    };
+    let skipping = || Names {
+        skip_generated: true,
+        ..Names::default()
+    };
+
+    let mut alone = skipping();
+    let _ = root.drive(&mut alone);
+    assert_eq!(alone.names, ["root", "gen", "a"]);
+
+    // The other visitors are still driven through the skipped children
+    let mut visitors = (skipping(), Counter::default());
+    let _ = root.drive(&mut visitors);
+    assert_eq!(visitors.0.names, alone.names);
+    assert_eq!(visitors.1.modules, 4);
+}
+
+#[test]
+fn test_tuple_visitor_break() {
+    #[derive(Visitor)]
+    #[visitor(Module(enter), break = "String")]
+    struct FindGenerated;
+
+    impl FindGenerated {
+        fn enter_module(&mut self, module: &Module) -> ControlFlow<String> {
+            if module.generated {
+                ControlFlow::Break(module.name.clone())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    #[derive(Default, Visitor)]
+    #[visitor(Module(enter), break = "String")]
+    struct Count(usize);
+
+    impl Count {
+        fn enter_module(&mut self, _module: &Module) {
+            self.0 += 1;
+        }
+    }
+
+    let root = Module {
+        name: "root".into(),
+        generated: false,
+        children: vec![Module {
+            name: "gen".into(),
+            generated: true,
+            children: vec![],
+        }],
+    };
+    let mut visitors = (Count::default(), FindGenerated, Count::default());
+    let flow = root.drive(&mut visitors);
+    assert_eq!(flow, ControlFlow::Break("gen".to_string()));
+    // Visitors after the one that breaks aren't notified of the item
+    assert_eq!((visitors.0 .0, visitors.2 .0), (2, 1));
+}
+
+#[test]
+fn test_tuple_visitor_mut() {
+    #[derive(VisitorMut)]
+    #[visitor(Module(enter))]
+    struct Shout;
+
+    impl Shout {
+        fn enter_module(&mut self, module: &mut Module) {
+            module.name = module.name.to_uppercase();
+        }
+    }
+
+    let mut root = Module {
+        name: "root".into(),
+        generated: false,
+        children: vec![Module {
+            name: "a".into(),
+            generated: false,
+            children: vec![],
+        }],
+    };
+    let mut visitors = (Shout, Counter::default());
+    let _ = root.drive_mut(&mut visitors);
+    assert_eq!(visitors.1.modules, 2);
+    assert_eq!(root.children[0].name, "A");
+}
+
+#[test]
+fn test_tuple_visitor_revisit() {
+    #[derive(Drive)]
+    struct Imports(Vec<Rc<Module>>);
+
+    #[derive(Default, Visitor)]
+    #[visitor(Module(enter), shared = "allocations")]
+    struct Unique {
+        allocations: Allocations,
+        modules: usize,
+    }
+
+    impl Unique {
+        fn enter_module(&mut self, _module: &Module) {
+            self.modules += 1;
+        }
+    }
+
+    let shared = Rc::new(Module {
+        name: "std".into(),
+        generated: false,
+        children: vec![Module {
+            name: "io".into(),
+            generated: false,
+            children: vec![],
+        }],
+    });
+    let imports = Imports(vec![shared.clone(), shared]);
+    let mut visitors = (Unique::default(), Counter::default());
+    let _ = imports.drive(&mut visitors);
+    assert_eq!(visitors.0.modules, 2);
+    assert_eq!(visitors.1.modules, 4);
+}
+
+#[test]
+fn test_tuple_visitor_skip_after_break() {
+    #[derive(Visitor)]
+    #[visitor(Module(enter), break = "()")]
+    struct FindHidden;
+
+    impl FindHidden {
+        fn enter_module(&mut self, module: &Module) -> ControlFlow<()> {
+            if module.name == "hidden" {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    let root = Module {
+        name: "root".into(),
+        generated: false,
+        children: vec![Module {
+            name: "gen".into(),
+            generated: true,
+            children: vec![Module {
+                name: "hidden".into(),
+                generated: false,
+                children: vec![],
+            }],
+        }],
+    };
+    let skipping = Names {
+        skip_generated: true,
+        ..Names::default()
+    };
+    let mut visitors = (skipping, FindHidden);
+    let flow = root.drive(&mut visitors);
+    assert_eq!(flow, ControlFlow::Break(()));
+    assert_eq!(visitors.0.names, ["root", "gen"]);
+
+    // Breaking inside of the skipped children doesn't affect the next traversal
+    let other = Module {
+        name: "other".into(),
+        generated: false,
+        children: vec![],
+    };
+    assert!(other.drive(&mut visitors).is_continue());
+    assert_eq!(visitors.0.names, ["root", "gen", "other"]);
+}
@@ -0,0 +1,96 @@
+use std::{ops::ControlFlow, rc::Rc, sync::Arc};
+
+use derive_visitor::{visitor_enter_fn_mut, DriveMut, Event, Unshare, VisitorMut};
+
+#[derive(Clone, DriveMut)]
+#[drive(unshare = "clone")]
+struct Expr {
+    #[drive(skip)]
+    value: u32,
+    operands: Vec<Arc<Expr>>,
+}
+
+#[derive(DriveMut)]
+#[drive(unshare = "skip")]
+struct Frozen(#[drive(skip)] u32);
+
+#[derive(DriveMut)]
+#[drive(unshare = "panic")]
+struct Unique;
+
+struct Counted(u32);
+
+impl DriveMut for Counted {
+    fn drive_mut<V: VisitorMut>(&mut self, visitor: &mut V) -> ControlFlow<V::Break> {
+        visitor.visit(self, Event::Enter)?;
+        visitor.visit(self, Event::Exit)?;
+        ControlFlow::Continue(())
+    }
+}
+
+impl Unshare for Counted {
+    fn unshare(&self) -> Option<Self> {
+        Some(Counted(self.0 + 100))
+    }
+}
+
+#[test]
+fn test_unshare_clone() {
+    let leaf = Arc::new(Expr {
+        value: 1,
+        operands: vec![],
+    });
+    let mut expr = Expr {
+        value: 2,
+        operands: vec![
+            leaf.clone(),
+            Arc::new(Expr {
+                value: 3,
+                operands: vec![],
+            }),
+        ],
+    };
+    let unique = Arc::as_ptr(&expr.operands[1]);
+
+    let _ = expr.drive_mut(&mut visitor_enter_fn_mut(|expr: &mut Expr| {
+        expr.value *= 10;
+    }));
+    assert_eq!(leaf.value, 1);
+    assert_eq!(expr.operands[0].value, 10);
+    assert!(!Arc::ptr_eq(&leaf, &expr.operands[0]));
+    // Values that aren't shared are visited in place
+    assert_eq!(Arc::as_ptr(&expr.operands[1]), unique);
+    assert_eq!(expr.operands[1].value, 30);
+}
+
+#[test]
+fn test_unshare_skip() {
+    let shared = Rc::new(Frozen(1));
+    let mut values = vec![shared.clone(), Rc::new(Frozen(2))];
+    let _ = values.drive_mut(&mut visitor_enter_fn_mut(|frozen: &mut Frozen| {
+        frozen.0 += 10;
+    }));
+    assert_eq!(values[0].0, 1);
+    assert_eq!(values[1].0, 12);
+    assert!(Rc::ptr_eq(&values[0], &shared));
+}
+
+#[test]
+fn test_unshare_manual() {
+    let shared = Arc::new(Counted(1));
+    let mut value = shared.clone();
+    let mut seen = Vec::new();
+    let _ = value.drive_mut(&mut visitor_enter_fn_mut(|counted: &mut Counted| {
+        seen.push(counted.0);
+    }));
+    assert_eq!(seen, [101]);
+    assert_eq!(shared.0, 1);
+}
+
+#[test]
+#[should_panic(expected = "cannot drive a VisitorMut through a shared Unique")]
+fn test_unshare_panic() {
+    let shared = Arc::new(Unique);
+    let mut value = shared.clone();
+    let _ = value.drive_mut(&mut visitor_enter_fn_mut(|_: &mut Unique| {}));
+}
@@ -0,0 +1,151 @@
+use derive_visitor::{Container, ContainerKind, Drive, DriveMut, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Statement(#[drive(skip)] String);
+
+// Statements are parsed on demand from the source
+#[derive(Drive, DriveMut)]
+#[drive(children = "Block::statements")]
+struct Block {
+    #[drive(skip)]
+    source: String,
+}
+
+impl Block {
+    fn statements(&self) -> Statement {
+        Statement(self.source.trim().to_string())
+    }
+}
+
+#[derive(Drive, DriveMut)]
+struct Import(#[drive(skip)] &'static str);
+
+// Imports are kept in private storage, the entry point comes last
+#[derive(Drive, DriveMut)]
+#[drive(children_ref = "Module::imports", children_mut = "Module::imports_mut")]
+struct Module {
+    body: Block,
+    #[drive(skip)]
+    imports: Vec<Import>,
+    #[drive(skip)]
+    prelude: Option<Import>,
+}
+
+impl Module {
+    fn imports(&self) -> impl Iterator<Item = &Import> {
+        self.prelude.iter().chain(&self.imports)
+    }
+
+    fn imports_mut(&mut self) -> &mut Vec<Import> {
+        &mut self.imports
+    }
+}
+
+#[derive(VisitorMut)]
+#[visitor(Import(enter))]
+struct Qualify;
+
+impl Qualify {
+    fn enter_import(&mut self, import: &mut Import) {
+        import.0 = match import.0 {
+            "io" => "std::io",
+            other => other,
+        };
+    }
+}
+
+#[test]
+fn test_virtual_children_value() {
+    #[derive(Default, Visitor)]
+    #[visitor(Statement(enter))]
+    struct Statements(Vec<String>);
+
+    impl Statements {
+        fn enter_statement(&mut self, statement: &Statement) {
+            self.0.push(statement.0.clone());
+        }
+    }
+
+    let block = Block {
+        source: " main() ".to_string(),
+    };
+    let mut statements = Statements::default();
+    let _ = block.drive(&mut statements);
+    assert_eq!(statements.0, ["main()"]);
+}
+
+#[test]
+fn test_virtual_children_iterator() {
+    // Checks that children come after the fields, and before the module is exited
+    #[derive(Default, Visitor)]
+    #[visitor(
+        Statement(enter),
+        Import(enter),
+        Module(exit),
+        container(enter = "enter_container")
+    )]
+    struct Imports {
+        statements: usize,
+        imports: Vec<&'static str>,
+        containers: Vec<Container>,
+        exited: bool,
+    }
+
+    impl Imports {
+        fn enter_statement(&mut self, _statement: &Statement) {
+            self.statements += 1;
+        }
+        fn enter_import(&mut self, import: &Import) {
+            assert_eq!((self.statements, self.exited), (1, false));
+            self.imports.push(import.0);
+        }
+        fn exit_module(&mut self, _module: &Module) {
+            self.exited = true;
+        }
+        fn enter_container(&mut self, container: Container) {
+            self.containers.push(container);
+        }
+    }
+
+    let module = Module {
+        body: Block {
+            source: "run()".to_string(),
+        },
+        imports: vec![Import("io"), Import("fmt")],
+        prelude: Some(Import("prelude")),
+    };
+    let mut imports = Imports::default();
+    let _ = module.drive(&mut imports);
+    assert_eq!(imports.imports, ["prelude", "io", "fmt"]);
+    // The iterator is reported as a sequence
+    assert_eq!(
+        imports.containers,
+        [Container {
+            kind: ContainerKind::Sequence,
+            len: 3
+        }]
+    );
+    assert!(imports.exited);
+}
+
+#[test]
+fn test_virtual_children_mut() {
+    let mut module = Module {
+        body: Block {
+            source: "run()".to_string(),
+        },
+        imports: vec![Import("io"), Import("fmt")],
+        prelude: Some(Import("prelude")),
+    };
+    let _ = module.drive_mut(&mut Qualify);
+    assert_eq!(
+        module
+            .imports
+            .iter()
+            .map(|import| import.0)
+            .collect::<Vec<_>>(),
+        ["std::io", "fmt"]
+    );
+    // `children_mut` doesn't return the prelude
+    assert_eq!(module.prelude.map(|import| import.0), Some("prelude"));
+}
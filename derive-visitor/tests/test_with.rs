@@ -0,0 +1,213 @@
+use std::ops::ControlFlow;
+
+use derive_visitor::{Drive, DriveMut, Path, Visitor, VisitorMut};
+
+#[derive(Drive, DriveMut)]
+struct Page(#[drive(skip)] u32);
+
+#[derive(Drive)]
+enum Section {
+    #[drive(with = "drive_spread")]
+    Spread(Page, #[drive(skip)] bool, Page),
+    // Only the first page of an excerpt is read
+    #[drive(with = "drive_excerpt")]
+    Excerpt {
+        source: Page,
+        pages: Vec<Page>,
+    },
+    Single(Page),
+}
+
+fn drive_spread<V: Visitor>(
+    left: &Page,
+    right_to_left: &bool,
+    right: &Page,
+    visitor: &mut V,
+) -> ControlFlow<V::Break> {
+    if *right_to_left {
+        right.drive(visitor)?;
+        left.drive(visitor)
+    } else {
+        left.drive(visitor)?;
+        right.drive(visitor)
+    }
+}
+
+fn drive_excerpt<V: Visitor>(
+    _source: &Page,
+    pages: &[Page],
+    visitor: &mut V,
+) -> ControlFlow<V::Break> {
+    pages
+        .first()
+        .map_or(ControlFlow::Continue(()), |page| page.drive(visitor))
+}
+
+// Only the right-hand pages are driven through
+#[derive(Drive)]
+#[drive(with = "drive_recto")]
+struct Recto(Vec<Page>);
+
+fn drive_recto<V: Visitor>(recto: &Recto, visitor: &mut V) -> ControlFlow<V::Break> {
+    recto
+        .0
+        .iter()
+        .filter(|page| page.0 % 2 == 1)
+        .try_for_each(|page| page.drive(visitor))
+}
+
+#[derive(Default, Visitor)]
+#[visitor(Page(enter), Recto, path = "path")]
+struct Reader {
+    path: Path,
+    events: Vec<String>,
+}
+
+impl Reader {
+    fn enter_page(&mut self, page: &Page) {
+        self.events.push(format!("{} {}", self.path, page.0));
+    }
+    fn enter_recto(&mut self, _recto: &Recto) {
+        self.events.push("enter recto".into());
+    }
+    fn exit_recto(&mut self, _recto: &Recto) {
+        self.events.push("exit recto".into());
+    }
+}
+
+#[derive(Default, Visitor, VisitorMut)]
+#[visitor(Page(enter))]
+struct PageNumbers(Vec<u32>);
+
+impl PageNumbers {
+    fn enter_page(&mut self, page: &Page) {
+        self.0.push(page.0);
+    }
+}
+
+#[test]
+fn test_with_variant() {
+    let sections = vec![
+        Section::Spread(Page(1), true, Page(2)),
+        Section::Spread(Page(3), false, Page(4)),
+        Section::Excerpt {
+            source: Page(100),
+            pages: vec![Page(5), Page(6)],
+        },
+        Section::Single(Page(7)),
+    ];
+    let mut reader = Reader::default();
+    let _ = sections.drive(&mut reader);
+    assert_eq!(
+        reader.events,
+        [
+            "[0].Spread 2",
+            "[0].Spread 1",
+            "[1].Spread 3",
+            "[1].Spread 4",
+            "[2].Excerpt 5",
+            "[3].Single 7"
+        ]
+    );
+}
+
+#[test]
+fn test_with_type() {
+    let mut reader = Reader::default();
+    let _ = Recto(vec![Page(1), Page(2), Page(3)]).drive(&mut reader);
+    assert_eq!(reader.events, ["enter recto", " 1", " 3", "exit recto"]);
+}
+
+#[test]
+fn test_with_variant_mut() {
+    #[derive(DriveMut)]
+    enum Sheet {
+        // Only the front of a leaflet is numbered
+        #[drive(with = "drive_front")]
+        Leaflet(Page, Page),
+        Folio(Page, Page),
+    }
+
+    fn drive_front<V: VisitorMut>(
+        front: &mut Page,
+        _back: &mut Page,
+        visitor: &mut V,
+    ) -> ControlFlow<V::Break> {
+        front.drive_mut(visitor)
+    }
+
+    #[derive(Default, VisitorMut)]
+    #[visitor(Page(enter))]
+    struct Renumber(u32);
+
+    impl Renumber {
+        fn enter_page(&mut self, page: &mut Page) {
+            self.0 += 1;
+            page.0 = self.0;
+        }
+    }
+
+    let mut sheets = vec![
+        Sheet::Leaflet(Page(0), Page(0)),
+        Sheet::Folio(Page(0), Page(0)),
+    ];
+    let _ = sheets.drive_mut(&mut Renumber::default());
+    let numbers: Vec<_> = sheets
+        .iter()
+        .map(|sheet| match sheet {
+            Sheet::Leaflet(front, back) | Sheet::Folio(front, back) => (front.0, back.0),
+        })
+        .collect();
+    assert_eq!(numbers, [(1, 0), (2, 3)]);
+}
+
+#[derive(Drive, DriveMut)]
+struct Chapter {
+    #[drive(with_ref = "drive_backwards", with_mut = "drive_first_mut")]
+    pages: Vec<Page>,
+}
+
+fn drive_backwards<V: Visitor>(pages: &[Page], visitor: &mut V) -> ControlFlow<V::Break> {
+    pages.iter().rev().try_for_each(|page| page.drive(visitor))
+}
+
+fn drive_first_mut<V: VisitorMut>(pages: &mut [Page], visitor: &mut V) -> ControlFlow<V::Break> {
+    pages
+        .first_mut()
+        .map_or(ControlFlow::Continue(()), |page| page.drive_mut(visitor))
+}
+
+#[test]
+fn test_with_ref_and_with_mut() {
+    let mut chapter = Chapter {
+        pages: vec![Page(1), Page(2)],
+    };
+    let mut numbers = PageNumbers::default();
+    let _ = chapter.drive(&mut numbers);
+    assert_eq!(numbers.0, [2, 1]);
+
+    let mut numbers = PageNumbers::default();
+    let _ = chapter.drive_mut(&mut numbers);
+    assert_eq!(numbers.0, [1]);
+}
+
+#[test]
+fn test_with_mut_type() {
+    // Frozen pages can be read, but not changed
+    #[derive(Drive, DriveMut)]
+    #[drive(with_mut = "drive_nothing")]
+    struct Frozen(Page);
+
+    fn drive_nothing<V: VisitorMut>(
+        _frozen: &mut Frozen,
+        _visitor: &mut V,
+    ) -> ControlFlow<V::Break> {
+        ControlFlow::Continue(())
+    }
+
+    let mut frozen = Frozen(Page(1));
+    let mut numbers = PageNumbers::default();
+    let _ = frozen.drive(&mut numbers);
+    let _ = frozen.drive_mut(&mut numbers);
+    assert_eq!(numbers.0, [1]);
+}